@@ -1,7 +1,12 @@
+use std::env;
+
+use mill::interpreter::{Capabilities, Config};
+
 fn main() {
-	let source = std::fs::read_to_string("test.mill").unwrap();
+	let path = "test.mill";
+	let source = std::fs::read_to_string(path).unwrap();
 
-	let ast = match mill::parser::parse(source) {
+	let ast = match mill::parser::parse_module(source, path.to_owned()) {
 		Ok(a) => a,
 		Err(e) => {
 			println!("{e}");
@@ -9,10 +14,24 @@ fn main() {
 		},
 	};
 
-	match mill::interpreter::interpret(ast) {
+	for warning in &ast.warnings {
+		print!("{warning}");
+	}
+
+	// Scripts run from the command line are trusted with the working directory,
+	// and can import modules from the search path
+	let mut capabilities = Capabilities::trusted(env::current_dir().unwrap());
+	if let Some(search_path) = env::var_os("MILL_PATH") {
+		capabilities.fs_read.extend(env::split_paths(&search_path));
+	}
+	let config = Config {
+		capabilities,
+		..Config::default()
+	};
+	match mill::interpreter::interpret_with_config(ast, config) {
 		Ok(()) => {},
 		Err(e) => {
-			println!("{e:?}");
+			println!("{e}");
 		},
 	}
 }
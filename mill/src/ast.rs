@@ -1,32 +1,90 @@
+use crate::parser::{LineNumber, ParseWarning};
+
 pub type Integer = i32;
 pub type Float = f64;
 
-#[derive(Debug, Clone)]
-pub struct Ast(pub(crate) Vec<Statement>);
+#[derive(Debug)]
+pub struct Ast {
+	pub source_file: Option<String>,
+	pub(crate) statements: Vec<Statement>,
+	/// Names declared with `export let` or `export const` at the top level
+	pub(crate) exports: Vec<Identifier>,
+	pub warnings: Vec<ParseWarning>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+	pub line_number: LineNumber,
+	pub kind: StatementKind,
+}
+
+impl Statement {
+	/// Whether the statement has a `yield` in it, outside of nested functions
+	pub fn contains_yield(&self) -> bool {
+		match &self.kind {
+			StatementKind::Yield(_) => true,
+			StatementKind::Loop { body, .. }
+			| StatementKind::For { body, .. }
+			| StatementKind::While { body, .. }
+			| StatementKind::Block { body } => body.iter().any(Self::contains_yield),
+			StatementKind::If { branches } => branches
+				.iter()
+				.flat_map(|(_, body)| body)
+				.any(Self::contains_yield),
+			StatementKind::Try {
+				body,
+				catch,
+				finally,
+			} => body
+				.iter()
+				.chain(catch.iter().flat_map(|(_, body)| body))
+				.chain(finally.iter().flatten())
+				.any(Self::contains_yield),
+			_ => false,
+		}
+	}
+}
 
-#[derive(Debug, Clone)]
-pub enum Statement {
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatementKind {
 	Declaration {
-		name: Identifier,
+		pattern: Pattern,
 		initializer: Expression,
+		/// Declared with `const`, so the variables and everything in them can't be changed
+		constant: bool,
 	},
 	Assignment {
 		lvalue: Lvalue,
 		value: Expression,
 	},
+	CompoundAssignment {
+		lvalue: Lvalue,
+		operation: BinaryOperation,
+		value: Expression,
+	},
 	UnusedExpression(Expression),
 	Return(Expression),
-	Break,
-	Continue,
+	/// Leaves the loop with the label, or the innermost loop
+	Break {
+		label: Option<Identifier>,
+		/// The value of a `loop` expression, `null` if not given
+		value: Option<Expression>,
+	},
+	Continue {
+		label: Option<Identifier>,
+	},
 	Loop {
+		label: Option<Identifier>,
 		body: Vec<Statement>,
 	},
 	For {
-		loop_var: Identifier,
+		label: Option<Identifier>,
+		pattern: Pattern,
 		iterator: Expression,
 		body: Vec<Statement>,
 	},
 	While {
+		label: Option<Identifier>,
 		condition: Expression,
 		body: Vec<Statement>,
 	},
@@ -36,9 +94,27 @@ pub enum Statement {
 	Block {
 		body: Vec<Statement>,
 	},
+	Throw(Expression),
+	/// Suspends the generator the statement is in
+	Yield(Expression),
+	Try {
+		body: Vec<Statement>,
+		catch: Option<(Identifier, Vec<Statement>)>,
+		finally: Option<Vec<Statement>>,
+	},
+	/// `import "path" as name;`
+	Import {
+		path: String,
+		name: Identifier,
+	},
+	/// `from "path" import a, b;`
+	ImportNames {
+		path: String,
+		names: Vec<Identifier>,
+	},
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expression {
 	// Keyword literals
 	True,
@@ -50,39 +126,177 @@ pub enum Expression {
 	Integer(Integer),
 	Float(Float),
 	String(String),
-	Array(Vec<Expression>),
-	Map(Vec<(Expression, Expression)>),
+	Array(Vec<ArrayElement>),
+	Map(Vec<MapEntry>),
 
 	// Special stuff
-	Function(Vec<Identifier>, Vec<Statement>),
-	Call(Box<Expression>, Vec<Expression>),
-	Member(Box<Expression>, Identifier),
+	Function(Parameters, Vec<Statement>),
+	Call(Box<Self>, Arguments),
+	Member(Box<Self>, Identifier),
+	/// The value before a `?.`, which ends the optional chain around it early if it's `null`,
+	/// or if the member or index right after it is missing
+	NullSafe(Box<Self>),
+	/// Member accesses, indexes and calls containing a `?.`, `null` if the chain was ended early
+	OptionalChain(Box<Self>),
+	Match(Box<Self>, Vec<MatchArm>),
+	/// The value of the block of the first true condition, `null` if none is true
+	If {
+		branches: Vec<(Self, Vec<Statement>)>,
+	},
+	/// The value of the last statement if it's an expression, `if`, block or `loop`
+	Block(Vec<Statement>),
+	/// A `loop` whose value is given by the `break` that leaves it
+	Loop {
+		label: Option<Identifier>,
+		body: Vec<Statement>,
+	},
+	Slice(Box<Self>, Option<Box<Self>>, Option<Box<Self>>),
+	Range {
+		bounds: Box<[Self; 2]>,
+		step: Option<Box<Self>>,
+		inclusive: bool,
+	},
 
 	// Normal operations
-	UnaryOperation(Box<Expression>, UnaryOperation),
-	BinaryOperation(Box<[Expression; 2]>, BinaryOperation),
+	UnaryOperation(Box<Self>, UnaryOperation),
+	BinaryOperation(Box<[Self; 2]>, BinaryOperation),
+}
+impl Expression {
+	/// Whether the expression refers to a value stored in a variable, so that assigning into it is meaningful
+	pub fn is_place(&self) -> bool {
+		match self {
+			Self::Identifier(_) => true,
+			Self::Member(e, _) => e.is_place(),
+			Self::BinaryOperation(ei, BinaryOperation::Index) => ei[0].is_place(),
+			_ => false,
+		}
+	}
+}
+
+/// An element of an array literal or an argument of a call
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArrayElement {
+	Single(Expression),
+	/// Every element of an iterable
+	Spread(Expression),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MapEntry {
+	Pair(Expression, Expression),
+	/// Every entry of a map
+	Spread(Expression),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameters {
+	pub positional: Vec<Parameter>,
+	/// Collects any extra arguments into an array
+	pub rest: Option<Identifier>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+	pub pattern: Pattern,
+	/// Evaluated at call time when no argument is given
+	pub default: Option<Expression>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arguments {
+	pub positional: Vec<ArrayElement>,
+	pub keyword: Vec<(Identifier, Expression)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchArm {
+	pub pattern: Pattern,
+	pub guard: Option<Expression>,
+	pub body: Expression,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+	Wildcard,
+	Binding(Identifier),
+	/// Only literal expressions, negative numbers are folded into the literal
+	Literal(Expression),
+	/// Element patterns, and a pattern for the array of remaining elements
+	Array(Vec<Self>, Option<Box<Self>>),
+	/// Map keys are literal expressions, other keys are allowed in the value
+	Map(Vec<(Expression, Self)>),
+	Or(Vec<Self>),
+}
+impl Pattern {
+	/// Whether the pattern matches every value
+	pub fn is_irrefutable(&self) -> bool {
+		match self {
+			Self::Wildcard | Self::Binding(_) => true,
+			Self::Or(alternatives) => alternatives.iter().any(Self::is_irrefutable),
+			Self::Literal(_) | Self::Array(..) | Self::Map(_) => false,
+		}
+	}
+
+	/// The names bound by the pattern
+	pub fn bindings(&self) -> Vec<Identifier> {
+		let mut bindings = Vec::new();
+		self.collect_bindings(&mut bindings);
+		bindings
+	}
+
+	fn collect_bindings(&self, bindings: &mut Vec<Identifier>) {
+		match self {
+			Self::Binding(name) => bindings.push(name.clone()),
+			Self::Wildcard | Self::Literal(_) => {},
+			Self::Array(elements, rest) => {
+				for element in elements.iter().chain(rest.as_deref()) {
+					element.collect_bindings(bindings);
+				}
+			},
+			Self::Map(entries) => {
+				for (_, pattern) in entries {
+					pattern.collect_bindings(bindings);
+				}
+			},
+			// Every alternative must bind the same names for the pattern to be useful
+			Self::Or(alternatives) => {
+				if let Some(first) = alternatives.first() {
+					first.collect_bindings(bindings);
+				}
+			},
+		}
+	}
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Lvalue {
 	Identifier(Identifier),
 	Member(Box<Expression>, Identifier),
 	Index(Box<[Expression; 2]>),
+	/// Assigns each element of an array to the lvalues,
+	/// and the array of remaining elements to the rest lvalue
+	Array(Vec<Self>, Option<Box<Self>>),
+	/// Assigns the value of each key of a map to its lvalue.
+	/// Keys are literals, written as in `Pattern::Map`.
+	Map(Vec<(Expression, Self)>),
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum UnaryOperation {
 	Neg,
 	Not,
+	BitNot,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BinaryOperation {
 	Add,
 	Sub,
 	Mul,
 	Div,
 	Mod,
+	Pow,
+	FloorDiv,
 	Eq,
 	NoEq,
 	Lt,
@@ -92,6 +306,14 @@ pub enum BinaryOperation {
 	Index,
 	And,
 	Or,
+	/// The left operand unless it's `null`
+	Coalesce,
+	BitAnd,
+	BitOr,
+	BitXor,
+	Shl,
+	Shr,
+	In,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -0,0 +1,57 @@
+use crate::{
+	ast::{Identifier, Integer},
+	interpreter::{
+		error::InterpreterErrorKind,
+		state::{Scope, State},
+		value::{NativeFunction, Value},
+	},
+};
+
+const FUNCTIONS: &[NativeFunction] = &[
+	NativeFunction {
+		name: "len",
+		function: len,
+	},
+	NativeFunction {
+		name: "sort",
+		function: sort,
+	},
+];
+
+/// The scope underneath the global scope, holding the native functions
+pub fn scope() -> Scope {
+	let mut scope = Scope::new();
+	for &function in FUNCTIONS {
+		scope.variables.insert(
+			Identifier(function.name.to_owned()),
+			Value::NativeFunction(function),
+		);
+	}
+	scope
+}
+
+fn len(state: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	let [value] =
+		<[Value; 1]>::try_from(arguments).map_err(|_| InterpreterErrorKind::WrongArgumentCount)?;
+	let len = match value {
+		Value::String(string) => string.chars().count(),
+		Value::Array(array) => array.len(),
+		Value::Map(map) => map.len(),
+		Value::Range(range) => range.len(),
+		_ => return Err(InterpreterErrorKind::UnsupportedOperation),
+	};
+	// Lengths beyond the integer range can't be represented
+	Integer::try_from(len)
+		.map(Value::Integer)
+		.map_err(|_| InterpreterErrorKind::UnsupportedOperation)
+}
+/// Gives the elements of an array in the total order of values, keeping equal ones in order
+fn sort(state: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	let [value] =
+		<[Value; 1]>::try_from(arguments).map_err(|_| InterpreterErrorKind::WrongArgumentCount)?;
+	let Value::Array(mut array) = value else {
+		return Err(InterpreterErrorKind::ExpectedArray);
+	};
+	array.sort_by(Value::total_cmp);
+	Ok(Value::Array(array))
+}
@@ -0,0 +1,114 @@
+use std::{
+	env,
+	path::{Path, PathBuf},
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+	},
+	time::Instant,
+};
+
+/// Limits on a script, set by the program running it.
+/// Scripts can't catch the errors from exceeding them.
+#[derive(Debug, Clone)]
+pub struct Config {
+	/// How many calls of script functions can be nested before a `StackOverflow` error.
+	/// Deeply nested calls and code move onto stack segments allocated on the heap,
+	/// so this limit doesn't depend on the size of the host's stack.
+	pub max_call_depth: usize,
+	/// Statements, loop iterations and calls the script can run
+	pub fuel: Option<u64>,
+	pub deadline: Option<Instant>,
+	/// Bytes of strings, arrays and maps the script can allocate over its whole run,
+	/// including copies of existing values and entries added to maps.
+	/// Memory the script stops using isn't given back, so this bounds the total allocated,
+	/// not how much is in use at once.
+	pub allocation_limit: Option<usize>,
+	pub cancellation: Option<CancellationHandle>,
+	/// What the built-in modules let the script do, nothing by default
+	pub capabilities: Capabilities,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Self {
+			max_call_depth: 5000,
+			fuel: None,
+			deadline: None,
+			allocation_limit: None,
+			cancellation: None,
+			capabilities: Capabilities::default(),
+		}
+	}
+}
+
+/// Stops a running script from another thread
+#[derive(Debug, Clone, Default)]
+pub struct CancellationHandle(Arc<AtomicBool>);
+
+impl CancellationHandle {
+	pub fn new() -> Self { Self::default() }
+
+	pub fn cancel(&self) { self.0.store(true, Ordering::Relaxed); }
+
+	pub fn is_cancelled(&self) -> bool { self.0.load(Ordering::Relaxed) }
+}
+
+/// Side effects scripts are allowed through the built-in modules.
+/// Functions of a module check them when called, and fail with `PermissionDenied`.
+#[allow(clippy::struct_excessive_bools)] // Each one is an independent permission
+#[derive(Debug, Clone, Default)]
+pub struct Capabilities {
+	/// Directories whose contents can be read, including subdirectories
+	pub fs_read: Vec<PathBuf>,
+	/// Directories whose contents can be created, changed and removed
+	pub fs_write: Vec<PathBuf>,
+	pub env: bool,
+	/// Running other programs
+	pub process: bool,
+	pub clock: bool,
+	pub random: bool,
+}
+
+impl Capabilities {
+	/// Every capability, with the filesystem limited to `root`
+	pub fn trusted(root: impl Into<PathBuf>) -> Self {
+		let root = root.into();
+		Self {
+			fs_read: vec![root.clone()],
+			fs_write: vec![root],
+			env: true,
+			process: true,
+			clock: true,
+			random: true,
+		}
+	}
+
+	pub fn can_read(&self, path: &Path) -> bool { within(path, &self.fs_read) }
+
+	pub fn can_write(&self, path: &Path) -> bool { within(path, &self.fs_write) }
+}
+
+fn within(path: &Path, directories: &[PathBuf]) -> bool {
+	let Some(path) = resolve(path) else {
+		return false;
+	};
+	directories
+		.iter()
+		.filter_map(|directory| directory.canonicalize().ok())
+		.any(|directory| path.starts_with(directory))
+}
+
+/// Absolute path with symbolic links and `..` resolved, also for paths that don't exist yet.
+/// `None` if the missing part of the path has a `..`.
+fn resolve(path: &Path) -> Option<PathBuf> {
+	let mut existing = env::current_dir().ok()?.join(path);
+	let mut missing = Vec::new();
+	loop {
+		if let Ok(canonical) = existing.canonicalize() {
+			return Some(canonical.join(missing.into_iter().rev().collect::<PathBuf>()));
+		}
+		missing.push(existing.file_name()?.to_owned());
+		existing.pop();
+	}
+}
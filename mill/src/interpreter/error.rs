@@ -1,13 +1,242 @@
+use std::{
+	error::Error,
+	fmt::{self, Display, Formatter},
+};
+
+use crate::{
+	ast::{Identifier, Integer},
+	interpreter::{state::ControlFlow, value::Value},
+	parser::{LineNumber, ParseError},
+};
+
 #[derive(Debug)]
-pub enum InterpreterError {
+pub struct InterpreterError {
+	pub source_file: Option<String>,
+	pub line_number: LineNumber,
+	pub kind: InterpreterErrorKind,
+}
+#[derive(Debug, Clone)]
+pub enum InterpreterErrorKind {
 	Redeclaration,
 	UnknownIdentifier,
+	AssignmentToConstant(Identifier),
 	UnsupportedOperation,
 	ExpectedBool,
 	ExpectedFunction,
 	WrongArgumentCount,
+	/// Calls were nested deeper than `Config::max_call_depth`
+	StackOverflow,
+	OutOfFuel,
+	DeadlineExceeded,
+	AllocationLimitExceeded,
+	Cancelled,
+	UnknownKeywordArgument(Identifier),
+	DuplicateArgument(Identifier),
 	UpwardControlFlowReachedTopLevel,
 	LoopControlFlowReachedFunction,
+	YieldOutsideGenerator,
+	/// A generator was resumed by its own body
+	GeneratorRunning,
 	MapKeyNotHashable,
 	KeyNotInMap,
+	IndexOutOfBounds {
+		index: Integer,
+		length: usize,
+	},
+	InvalidShiftAmount,
+	DivisionByZero,
+	/// The result of integer arithmetic doesn't fit in an `Integer`
+	IntegerOverflow,
+	InvalidRangeStep,
+	/// A function needing at least one element was given an empty range
+	EmptyRange,
+	NotIterable,
+	NoMatch,
+	PatternMismatch,
+	ExpectedArray,
+	ExpectedMap,
+	ExpectedString,
+	WrongArrayLength {
+		expected: usize,
+		found: usize,
+	},
+	ModuleNotFound(String),
+	/// The chain of imports that leads back to a module still being evaluated
+	ImportCycle(Vec<String>),
+	ModuleParseError(ParseError),
+	NotExported(Identifier),
+	Io(String),
+	/// The script tried something `Config::capabilities` doesn't allow
+	PermissionDenied(String),
+	/// Text given to a built-in module isn't valid in its format
+	Decode {
+		format: &'static str,
+		line: usize,
+		column: usize,
+		message: String,
+	},
+	/// A value can't be represented in a format
+	Encode {
+		format: &'static str,
+		message: String,
+	},
+	/// A value thrown by the script with `throw`
+	Thrown(Value),
+	/// A `return`, or a `break` or `continue` of an outer loop, leaving a `loop` expression.
+	/// Turned back into control flow by the statement around the expression, so never seen by scripts.
+	ControlFlow(Box<ControlFlow>),
+	/// A `?.` found `null` or a missing member or index, turned into `null` by the optional chain around it
+	ShortCircuit,
+}
+
+impl InterpreterErrorKind {
+	/// Whether the error comes from a limit in `Config`, which scripts can't catch
+	pub fn is_limit(&self) -> bool {
+		matches!(
+			self,
+			Self::OutOfFuel
+				| Self::DeadlineExceeded
+				| Self::AllocationLimitExceeded
+				| Self::Cancelled
+		)
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Redeclaration => "Redeclaration",
+			Self::UnknownIdentifier => "UnknownIdentifier",
+			Self::AssignmentToConstant(_) => "AssignmentToConstant",
+			Self::UnsupportedOperation => "UnsupportedOperation",
+			Self::ExpectedBool => "ExpectedBool",
+			Self::ExpectedFunction => "ExpectedFunction",
+			Self::WrongArgumentCount => "WrongArgumentCount",
+			Self::StackOverflow => "StackOverflow",
+			Self::OutOfFuel => "OutOfFuel",
+			Self::DeadlineExceeded => "DeadlineExceeded",
+			Self::AllocationLimitExceeded => "AllocationLimitExceeded",
+			Self::Cancelled => "Cancelled",
+			Self::UnknownKeywordArgument(_) => "UnknownKeywordArgument",
+			Self::DuplicateArgument(_) => "DuplicateArgument",
+			Self::UpwardControlFlowReachedTopLevel => "UpwardControlFlowReachedTopLevel",
+			Self::LoopControlFlowReachedFunction => "LoopControlFlowReachedFunction",
+			Self::YieldOutsideGenerator => "YieldOutsideGenerator",
+			Self::GeneratorRunning => "GeneratorRunning",
+			Self::MapKeyNotHashable => "MapKeyNotHashable",
+			Self::KeyNotInMap => "KeyNotInMap",
+			Self::IndexOutOfBounds { .. } => "IndexOutOfBounds",
+			Self::InvalidShiftAmount => "InvalidShiftAmount",
+			Self::DivisionByZero => "DivisionByZero",
+			Self::IntegerOverflow => "IntegerOverflow",
+			Self::InvalidRangeStep => "InvalidRangeStep",
+			Self::EmptyRange => "EmptyRange",
+			Self::NotIterable => "NotIterable",
+			Self::NoMatch => "NoMatch",
+			Self::PatternMismatch => "PatternMismatch",
+			Self::ExpectedArray => "ExpectedArray",
+			Self::ExpectedMap => "ExpectedMap",
+			Self::ExpectedString => "ExpectedString",
+			Self::WrongArrayLength { .. } => "WrongArrayLength",
+			Self::ModuleNotFound(_) => "ModuleNotFound",
+			Self::ImportCycle(_) => "ImportCycle",
+			Self::ModuleParseError(_) => "ModuleParseError",
+			Self::NotExported(_) => "NotExported",
+			Self::Io(_) => "Io",
+			Self::PermissionDenied(_) => "PermissionDenied",
+			Self::Decode { .. } => "Decode",
+			Self::Encode { .. } => "Encode",
+			Self::Thrown(_) => "Thrown",
+			Self::ControlFlow(_) => "ControlFlow",
+			Self::ShortCircuit => "ShortCircuit",
+		}
+	}
+}
+
+impl Display for InterpreterErrorKind {
+	fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+		match self {
+			Self::Redeclaration => write!(f, "Variable already declared in this scope"),
+			Self::UnknownIdentifier => write!(f, "Unknown identifier"),
+			Self::AssignmentToConstant(name) => write!(f, "Can't change constant '{}'", name.0),
+			Self::UnsupportedOperation => write!(f, "Unsupported operation"),
+			Self::ExpectedBool => write!(f, "Expected a bool"),
+			Self::ExpectedFunction => write!(f, "Expected a function"),
+			Self::WrongArgumentCount => write!(f, "Wrong number of arguments"),
+			Self::StackOverflow => write!(f, "Maximum call depth exceeded"),
+			Self::OutOfFuel => write!(f, "Script ran out of fuel"),
+			Self::DeadlineExceeded => write!(f, "Script ran past its deadline"),
+			Self::AllocationLimitExceeded => write!(f, "Script exceeded its allocation limit"),
+			Self::Cancelled => write!(f, "Script was cancelled"),
+			Self::UnknownKeywordArgument(name) => {
+				write!(f, "Unknown keyword argument '{}'", name.0)
+			},
+			Self::DuplicateArgument(name) => {
+				write!(f, "Argument '{}' given more than once", name.0)
+			},
+			Self::UpwardControlFlowReachedTopLevel => {
+				write!(
+					f,
+					"'return', 'break' or 'continue' outside of a function or loop"
+				)
+			},
+			Self::LoopControlFlowReachedFunction => {
+				write!(f, "'break' or 'continue' outside of a loop")
+			},
+			Self::YieldOutsideGenerator => write!(f, "'yield' outside of a function"),
+			Self::GeneratorRunning => write!(f, "Generator resumed while it's running"),
+			Self::MapKeyNotHashable => write!(f, "Map key not hashable"),
+			Self::KeyNotInMap => write!(f, "Key not in map"),
+			Self::IndexOutOfBounds { index, length } => {
+				write!(f, "Index {index} out of bounds for length {length}")
+			},
+			Self::InvalidShiftAmount => write!(f, "Shift amount out of range"),
+			Self::DivisionByZero => write!(f, "Division by zero"),
+			Self::IntegerOverflow => write!(f, "Integer overflow"),
+			Self::InvalidRangeStep => write!(f, "Range step can't be zero"),
+			Self::EmptyRange => write!(f, "Range is empty"),
+			Self::NotIterable => write!(f, "Value not iterable"),
+			Self::NoMatch => write!(f, "No match arm matched the value"),
+			Self::PatternMismatch => write!(f, "Value doesn't match the pattern"),
+			Self::ExpectedArray => write!(f, "Expected an array"),
+			Self::ExpectedMap => write!(f, "Expected a map"),
+			Self::ExpectedString => write!(f, "Expected a string"),
+			Self::WrongArrayLength { expected, found } => {
+				write!(
+					f,
+					"Expected an array of length {expected}, found length {found}"
+				)
+			},
+			Self::ModuleNotFound(path) => write!(f, "Module \"{path}\" not found"),
+			Self::ImportCycle(chain) => write!(f, "Import cycle: {}", chain.join(" -> ")),
+			Self::ModuleParseError(error) => {
+				write!(f, "Couldn't parse module\n{}", error.to_string().trim_end())
+			},
+			Self::NotExported(name) => write!(f, "Module doesn't export '{}'", name.0),
+			Self::Io(message) => write!(f, "{message}"),
+			Self::PermissionDenied(action) => write!(f, "Not allowed to {action}"),
+			Self::Decode {
+				format,
+				line,
+				column,
+				message,
+			} => write!(f, "Invalid {format} at {line}:{column}: {message}"),
+			Self::Encode { format, message } => write!(f, "Can't encode as {format}: {message}"),
+			Self::Thrown(value) => write!(f, "Uncaught exception: {value:?}"),
+			Self::ControlFlow(_) => write!(f, "Control flow left a 'loop' expression"),
+			Self::ShortCircuit => write!(f, "'?.' outside of an optional chain"),
+		}
+	}
+}
+
+impl Display for InterpreterError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+		writeln!(
+			f,
+			"{}:{} - {}",
+			self.source_file.as_deref().unwrap_or("<source>"),
+			self.line_number,
+			self.kind
+		)
+	}
 }
+
+impl Error for InterpreterError {}
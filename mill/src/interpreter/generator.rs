@@ -0,0 +1,430 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+	ast::{Expression, Identifier, Pattern, Statement, StatementKind},
+	interpreter::{
+		error::InterpreterErrorKind,
+		interpret::{
+			error_value, evaluate_expression, finish_tail_call, in_module, interpret_statement,
+			nested_call, Iteration,
+		},
+		module::ModuleId,
+		pattern::destructure,
+		state::{ControlFlow, Scope, State},
+		value::Value,
+	},
+	parser::LineNumber,
+};
+
+/// A call of a function containing `yield`, which runs its body a piece at a time.
+/// Statements that contain a `yield` are tracked as frames so they can be resumed,
+/// every other statement is interpreted as usual.
+/// Copies of a generator share its progress, so advancing one advances them all.
+#[derive(Debug, Clone)]
+pub struct Generator(Rc<RefCell<Suspended>>);
+
+/// The progress of a generator
+#[derive(Debug)]
+struct Suspended {
+	/// Scopes of the body, kept here while suspended.
+	/// The first is the function's scope, the rest belong to scoped blocks.
+	scopes: Vec<Scope>,
+	/// The statements being executed, innermost last. Empty once finished.
+	frames: Vec<Frame>,
+	module: ModuleId,
+}
+
+#[derive(Debug, Clone)]
+enum Frame {
+	/// The statements left in a block, which owns a scope if `scoped`
+	Block {
+		body: std::vec::IntoIter<Statement>,
+		scoped: bool,
+	},
+	Loop {
+		label: Option<Identifier>,
+		body: Vec<Statement>,
+	},
+	While {
+		label: Option<Identifier>,
+		line_number: LineNumber,
+		condition: Expression,
+		body: Vec<Statement>,
+	},
+	For {
+		label: Option<Identifier>,
+		line_number: LineNumber,
+		pattern: Pattern,
+		iteration: Iteration,
+		body: Vec<Statement>,
+	},
+	/// The `catch` and `finally` are taken once they start running
+	Try {
+		catch: Option<(Identifier, Vec<Statement>)>,
+		finally: Option<Vec<Statement>>,
+		/// What to continue with after `finally`
+		pending: Option<Completion>,
+	},
+}
+
+/// How a statement finished, carried through the frames being unwound
+#[derive(Debug, Clone)]
+enum Completion {
+	Normal,
+	Break(Option<Identifier>),
+	Continue(Option<Identifier>),
+	Return,
+	Error(InterpreterErrorKind),
+}
+impl Completion {
+	fn from_result(state: &mut State, result: Result<ControlFlow, InterpreterErrorKind>) -> Self {
+		let result = match result {
+			// Left a `loop` expression evaluated by a frame
+			Err(InterpreterErrorKind::ControlFlow(control_flow)) => Ok(*control_flow),
+			result => result,
+		};
+		// The generator's return value is discarded, but the call still has to happen
+		match result.and_then(|control_flow| finish_tail_call(state, control_flow)) {
+			Ok(ControlFlow::Normal) => Self::Normal,
+			Ok(ControlFlow::Break(label, _)) => Self::Break(label),
+			Ok(ControlFlow::Continue(label)) => Self::Continue(label),
+			Ok(ControlFlow::Return(_)) => Self::Return,
+			Ok(ControlFlow::TailCall { .. }) => unreachable!("tail calls were just finished"),
+			Err(error) => Self::Error(error),
+		}
+	}
+}
+
+impl Generator {
+	pub fn new(scope: Scope, body: Vec<Statement>, module: ModuleId) -> Self {
+		Self(Rc::new(RefCell::new(Suspended {
+			scopes: vec![scope],
+			frames: vec![Frame::Block {
+				body: body.into_iter(),
+				scoped: false,
+			}],
+			module,
+		})))
+	}
+
+	/// Runs the body up to the next `yield`, returning `None` once it has finished
+	pub fn resume(&self, state: &mut State) -> Result<Option<Value>, InterpreterErrorKind> {
+		// The body resuming its own generator
+		let Ok(mut suspended) = self.0.try_borrow_mut() else {
+			return Err(InterpreterErrorKind::GeneratorRunning);
+		};
+		suspended.resume(state)
+	}
+}
+
+impl Suspended {
+	fn resume(&mut self, state: &mut State) -> Result<Option<Value>, InterpreterErrorKind> {
+		if self.frames.is_empty() {
+			return Ok(None);
+		}
+		let line_number = state.line_number;
+		let result = nested_call(state, |state| {
+			in_module(state, self.module, |state| {
+				let base = state.stack.len();
+				state.stack.append(&mut self.scopes);
+				let result = self.run(state, base);
+				self.scopes = state.stack.split_off(base);
+				result
+			})
+		});
+		match result {
+			Ok(_) => state.line_number = line_number,
+			Err(_) => self.frames.clear(),
+		}
+		result
+	}
+
+	fn run(
+		&mut self,
+		state: &mut State,
+		base: usize,
+	) -> Result<Option<Value>, InterpreterErrorKind> {
+		while let Some(frame) = self.frames.pop() {
+			let completion = match frame {
+				Frame::Block { mut body, scoped } => {
+					let Some(statement) = body.next() else {
+						if scoped {
+							state.pop();
+						}
+						continue;
+					};
+					self.frames.push(Frame::Block { body, scoped });
+
+					if statement.contains_yield() {
+						state.line_number = statement.line_number;
+						match self.enter(state, statement.kind) {
+							Ok(Some(item)) => return Ok(Some(item)),
+							Ok(None) => Completion::Normal,
+							Err(error) => Completion::from_result(state, Err(error)),
+						}
+					} else {
+						let result = interpret_statement(state, statement);
+						Completion::from_result(state, result)
+					}
+				},
+				// The body finished, or hasn't started yet
+				Frame::Loop { label, body } => match state.step() {
+					Ok(()) => {
+						self.frames.push(Frame::Loop {
+							label,
+							body: body.clone(),
+						});
+						self.push_block(state, body, Scope::new());
+						Completion::Normal
+					},
+					Err(error) => Completion::Error(error),
+				},
+				Frame::While {
+					label,
+					line_number,
+					condition,
+					body,
+				} => {
+					state.line_number = line_number;
+					match state
+						.step()
+						.and_then(|()| evaluate_expression(state, condition.clone()))
+					{
+						Ok(Value::Bool(true)) => {
+							self.frames.push(Frame::While {
+								label,
+								line_number,
+								condition,
+								body: body.clone(),
+							});
+							self.push_block(state, body, Scope::new());
+							Completion::Normal
+						},
+						Ok(Value::Bool(false)) => Completion::Normal,
+						Ok(_) => Completion::Error(InterpreterErrorKind::ExpectedBool),
+						Err(error) => Completion::from_result(state, Err(error)),
+					}
+				},
+				Frame::For {
+					label,
+					line_number,
+					pattern,
+					mut iteration,
+					body,
+				} => {
+					state.line_number = line_number;
+					match iteration.next(state) {
+						Ok(Some(item)) => {
+							let mut bindings = Vec::new();
+							match destructure(&pattern, &item, &mut bindings) {
+								Ok(()) => {
+									let mut scope = Scope::new();
+									scope.variables.extend(bindings);
+									self.frames.push(Frame::For {
+										label,
+										line_number,
+										pattern,
+										iteration,
+										body: body.clone(),
+									});
+									self.push_block(state, body, scope);
+									Completion::Normal
+								},
+								Err(error) => Completion::Error(error),
+							}
+						},
+						Ok(None) => Completion::Normal,
+						Err(error) => Completion::Error(error),
+					}
+				},
+				// The block of the `try` or `catch` finished normally
+				Frame::Try {
+					finally: Some(finally),
+					..
+				} => {
+					self.frames.push(Frame::Try {
+						catch: None,
+						finally: None,
+						pending: Some(Completion::Normal),
+					});
+					self.push_block(state, finally, Scope::new());
+					Completion::Normal
+				},
+				Frame::Try { pending, .. } => pending.unwrap_or(Completion::Normal),
+			};
+
+			if let Completion::Error(_) | Completion::Return = completion {
+				// Scopes of the statements that failed or made a tail call are never popped
+				let scoped = self
+					.frames
+					.iter()
+					.filter(|frame| matches!(frame, Frame::Block { scoped: true, .. }))
+					.count();
+				state.stack.truncate(base + 1 + scoped);
+			}
+			self.unwind(state, completion)?;
+		}
+		Ok(None)
+	}
+
+	/// Starts running a statement that contains a `yield`
+	fn enter(
+		&mut self,
+		state: &mut State,
+		statement: StatementKind,
+	) -> Result<Option<Value>, InterpreterErrorKind> {
+		match statement {
+			StatementKind::Yield(expression) => {
+				return Ok(Some(evaluate_expression(state, expression)?))
+			},
+			StatementKind::Block { body } => self.push_block(state, body, Scope::new()),
+			StatementKind::If { branches } => {
+				for (condition, body) in branches {
+					let Value::Bool(condition) = evaluate_expression(state, condition)? else {
+						return Err(InterpreterErrorKind::ExpectedBool);
+					};
+					if condition {
+						self.push_block(state, body, Scope::new());
+						break;
+					}
+				}
+			},
+			StatementKind::Loop { label, body } => self.frames.push(Frame::Loop { label, body }),
+			StatementKind::While {
+				label,
+				condition,
+				body,
+			} => self.frames.push(Frame::While {
+				label,
+				line_number: state.line_number,
+				condition,
+				body,
+			}),
+			StatementKind::For {
+				label,
+				pattern,
+				iterator,
+				body,
+			} => {
+				let iteration = Iteration::new(evaluate_expression(state, iterator)?)?;
+				self.frames.push(Frame::For {
+					label,
+					line_number: state.line_number,
+					pattern,
+					iteration,
+					body,
+				});
+			},
+			StatementKind::Try {
+				body,
+				catch,
+				finally,
+			} => {
+				self.frames.push(Frame::Try {
+					catch,
+					finally,
+					pending: None,
+				});
+				self.push_block(state, body, Scope::new());
+			},
+			_ => unreachable!("only statements with blocks can contain `yield`"),
+		}
+		Ok(None)
+	}
+
+	fn push_block(&mut self, state: &mut State, body: Vec<Statement>, scope: Scope) {
+		state.stack.push(scope);
+		self.frames.push(Frame::Block {
+			body: body.into_iter(),
+			scoped: true,
+		});
+	}
+
+	/// Pops frames until one handles the completion.
+	/// Errors that aren't caught end the generator.
+	fn unwind(
+		&mut self,
+		state: &mut State,
+		completion: Completion,
+	) -> Result<(), InterpreterErrorKind> {
+		match completion {
+			Completion::Normal => return Ok(()),
+			// Limits stop the script, so they skip `catch` and `finally`
+			Completion::Error(error) if error.is_limit() => return Err(error),
+			_ => {},
+		}
+		while let Some(frame) = self.frames.pop() {
+			match frame {
+				Frame::Block { scoped, .. } => {
+					if scoped {
+						state.pop();
+					}
+				},
+				Frame::Loop { ref label, .. }
+				| Frame::While { ref label, .. }
+				| Frame::For { ref label, .. } => match &completion {
+					Completion::Break(target)
+						if ControlFlow::targets(target.as_ref(), label.as_ref()) =>
+					{
+						return Ok(())
+					},
+					Completion::Continue(target)
+						if ControlFlow::targets(target.as_ref(), label.as_ref()) =>
+					{
+						self.frames.push(frame);
+						return Ok(());
+					},
+					_ => {},
+				},
+				// A completion from inside `finally` replaces the pending one
+				Frame::Try {
+					pending: Some(_), ..
+				} => {},
+				Frame::Try {
+					catch: Some((name, body)),
+					finally,
+					pending: None,
+				} if matches!(completion, Completion::Error(_)) => {
+					let Completion::Error(error) = completion else {
+						unreachable!()
+					};
+					let mut scope = Scope::new();
+					scope
+						.variables
+						.insert(name, error_value(error, state.line_number));
+					// The error may have come from a function of another module
+					state.module = self.module;
+					self.frames.push(Frame::Try {
+						catch: None,
+						finally,
+						pending: None,
+					});
+					self.push_block(state, body, scope);
+					return Ok(());
+				},
+				Frame::Try {
+					finally: Some(finally),
+					..
+				} => {
+					state.module = self.module;
+					self.frames.push(Frame::Try {
+						catch: None,
+						finally: None,
+						pending: Some(completion),
+					});
+					self.push_block(state, finally, Scope::new());
+					return Ok(());
+				},
+				Frame::Try { .. } => {},
+			}
+		}
+		// The completion left the body of the function
+		match completion {
+			Completion::Normal | Completion::Return => Ok(()),
+			Completion::Break(_) | Completion::Continue(_) => {
+				Err(InterpreterErrorKind::LoopControlFlowReachedFunction)
+			},
+			Completion::Error(error) => Err(error),
+		}
+	}
+}
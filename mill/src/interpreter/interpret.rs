@@ -1,204 +1,617 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fs, path::Path};
 
 use crate::{
-	ast::{Ast, BinaryOperation, Expression, Identifier, Lvalue, Statement, UnaryOperation},
+	ast::{
+		Arguments, ArrayElement, Ast, BinaryOperation, Expression, Float, Identifier, Integer,
+		Lvalue, MapEntry, Parameters, Pattern, Statement, StatementKind, UnaryOperation,
+	},
 	interpreter::{
-		error::InterpreterError,
+		builtins,
+		config::Config,
+		error::{InterpreterError, InterpreterErrorKind},
+		generator::Generator,
+		module::{self, Module, ModuleId},
+		pattern::{destructure, match_pattern},
 		state::{ControlFlow, Scope, State},
-		value::Value,
+		stdlib,
+		value::{HashableValue, Range, Value},
 	},
+	parser::{self, LineNumber},
+	stack,
 };
 
+/// Arguments passed by name, in the order they were given
+type KeywordArguments = Vec<(Identifier, Value)>;
+
+/// The key of the map that a map delegates missing members to
+const PROTOTYPE_KEY: &str = "proto";
+
 pub fn interpret(ast: Ast) -> Result<(), InterpreterError> {
-	let mut state = State::new();
+	interpret_with_config(ast, Config::default())
+}
+pub fn interpret_with_config(ast: Ast, config: Config) -> Result<(), InterpreterError> {
+	let mut state = State::new(config);
+	let path = ast
+		.source_file
+		.as_ref()
+		.and_then(|file| Path::new(file).canonicalize().ok());
+	state.modules.push(Module::new(ast.source_file, path));
+	state.stack.push(builtins::scope());
 	state.push();
-	for statement in ast.0 {
-		match interpret_statement(&mut state, statement)? {
-			ControlFlow::Normal => {},
-			c => return Err(InterpreterError::UpwardControlFlowReachedTopLevel),
-		}
+	for statement in ast.statements {
+		let error = match interpret_statement(&mut state, statement) {
+			Ok(ControlFlow::Normal) => continue,
+			Ok(c) => InterpreterErrorKind::UpwardControlFlowReachedTopLevel,
+			Err(kind) => kind,
+		};
+		return Err(InterpreterError {
+			source_file: state.modules[state.module].name.clone(),
+			line_number: state.line_number,
+			kind: error,
+		});
 	}
 	dbg!(state);
 	Ok(())
 }
-fn interpret_statement(
+pub fn interpret_statement(
 	state: &mut State,
 	statement: Statement,
-) -> Result<ControlFlow, InterpreterError> {
-	match statement {
-		Statement::Declaration { name, initializer } => {
-			if state.current_scope().variables.contains_key(&name) {
-				return Err(InterpreterError::Redeclaration);
-			}
-
+) -> Result<ControlFlow, InterpreterErrorKind> {
+	match stack::grow(|| execute_statement(state, statement)) {
+		Err(InterpreterErrorKind::ControlFlow(control_flow)) => Ok(*control_flow),
+		result => result,
+	}
+}
+fn execute_statement(
+	state: &mut State,
+	statement: Statement,
+) -> Result<ControlFlow, InterpreterErrorKind> {
+	let Statement { line_number, kind } = statement;
+	state.line_number = line_number;
+	state.step()?;
+	match kind {
+		StatementKind::Declaration {
+			pattern,
+			initializer,
+			constant,
+		} => {
 			let value = evaluate_expression(state, initializer)?;
-
-			state.current_scope().variables.insert(name, value);
+			let mut bindings = Vec::new();
+			destructure(&pattern, &value, &mut bindings)?;
+			for (name, value) in bindings {
+				declare(state, name, value, constant)?;
+			}
 		},
-		Statement::Assignment { lvalue, value } => {
+		StatementKind::Assignment { lvalue, value } => {
 			let value = evaluate_expression(state, value)?;
-			let lvalue: &mut Value = match lvalue {
-				Lvalue::Identifier(identifier) => lookup_mut(state, &identifier)?,
-				_ => todo!(),
-			};
-			*lvalue = value;
+			assign(state, lvalue, value)?;
 		},
-		Statement::UnusedExpression(expression) => _ = evaluate_expression(state, expression)?,
-		Statement::Block { body } => {
-			state.push();
-			for statement in body {
-				match interpret_statement(state, statement)? {
-					ControlFlow::Normal => {},
-					c => return Ok(c),
-				}
-			}
-			state.pop();
+		StatementKind::CompoundAssignment {
+			lvalue,
+			operation,
+			value,
+		} => {
+			let (variable, path) = evaluate_lvalue(state, lvalue)?;
+			let value = evaluate_expression(state, value)?;
+			let current = place_mut(state, &variable, path.clone(), false)?.clone();
+			state.allocate(current.heap_size() + allocation_size(&current, &value, operation))?;
+			*place_mut(state, &variable, path, false)? =
+				binary_operation(current, value, operation)?;
 		},
-		Statement::If { branches } => {
+		StatementKind::UnusedExpression(expression) => _ = evaluate_expression(state, expression)?,
+		StatementKind::Block { body } => match interpret_block(state, Scope::new(), body)? {
+			ControlFlow::Normal => {},
+			c => return Ok(c),
+		},
+		StatementKind::If { branches } => {
 			for (condition, body) in branches {
 				let Value::Bool(condition) = evaluate_expression(state, condition)? else {
-					return Err(InterpreterError::ExpectedBool);
+					return Err(InterpreterErrorKind::ExpectedBool);
 				};
 				if condition {
-					state.push();
-					for statement in body {
-						match interpret_statement(state, statement)? {
-							ControlFlow::Normal => {},
-							c => {
-								state.pop();
-								return Ok(c);
-							},
-						}
+					match interpret_block(state, Scope::new(), body)? {
+						ControlFlow::Normal => {},
+						c => return Ok(c),
 					}
-					state.pop();
 					break;
 				}
 			}
 		},
-		Statement::Return(expression) => {
+		// Methods are left out, since `self` is written back after they return
+		StatementKind::Return(Expression::Call(function, arguments))
+			if !matches!(*function, Expression::Member(..)) =>
+		{
+			let function = evaluate_expression(state, *function)?;
+			let (arguments, keyword_arguments) = evaluate_arguments(state, arguments)?;
+			return Ok(ControlFlow::TailCall {
+				function,
+				arguments,
+				keyword_arguments,
+			});
+		},
+		StatementKind::Return(expression) => {
 			return Ok(ControlFlow::Return(evaluate_expression(state, expression)?))
 		},
-		Statement::Break => return Ok(ControlFlow::Break),
-		Statement::Continue => return Ok(ControlFlow::Continue),
-		Statement::Loop { body } => {
-			let start_len = state.stack.len();
-			'main_loop: loop {
-				let local_body = body.clone();
-				state.push();
-				for statement in local_body {
-					match interpret_statement(state, statement)? {
-						ControlFlow::Normal => {},
-						ControlFlow::Return(e) => return Ok(ControlFlow::Return(e)),
-						ControlFlow::Break => break 'main_loop,
-						ControlFlow::Continue => break,
-					}
-				}
-				state.pop();
-			}
-			state.pop();
-			assert_eq!(start_len, state.stack.len());
-		},
-		Statement::While { condition, body } => {
-			let start_len = state.stack.len();
-			'main_loop: loop {
-				let local_condition = condition.clone();
-				let Value::Bool(local_condition) = evaluate_expression(state, local_condition)?
-				else {
-					return Err(InterpreterError::ExpectedBool);
-				};
-				let local_body = body.clone();
-
-				state.push();
-				for statement in local_body {
-					match interpret_statement(state, statement)? {
-						ControlFlow::Normal => {},
-						ControlFlow::Return(e) => return Ok(ControlFlow::Return(e)),
-						ControlFlow::Break => break 'main_loop,
-						ControlFlow::Continue => break,
-					}
-				}
-				state.pop();
+		StatementKind::Yield(_) => return Err(InterpreterErrorKind::YieldOutsideGenerator),
+		StatementKind::Break { label, value } => {
+			let value = match value {
+				Some(value) => evaluate_expression(state, value)?,
+				None => Value::Null,
+			};
+			return Ok(ControlFlow::Break(label, value));
+		},
+		StatementKind::Continue { label } => return Ok(ControlFlow::Continue(label)),
+		StatementKind::Loop { label, body } => loop {
+			state.step()?;
+			match interpret_block(state, Scope::new(), body.clone())? {
+				ControlFlow::Normal => {},
+				ControlFlow::Continue(target)
+					if ControlFlow::targets(target.as_ref(), label.as_ref()) => {},
+				ControlFlow::Break(target, _)
+					if ControlFlow::targets(target.as_ref(), label.as_ref()) =>
+				{
+					break
+				},
+				c => return Ok(c),
 			}
-			assert_eq!(start_len, state.stack.len());
 		},
-		Statement::For {
-			loop_var,
+		StatementKind::While {
+			label,
+			condition,
+			body,
+		} => loop {
+			state.line_number = line_number;
+			state.step()?;
+			let Value::Bool(condition) = evaluate_expression(state, condition.clone())? else {
+				return Err(InterpreterErrorKind::ExpectedBool);
+			};
+			if !condition {
+				break;
+			}
+			match interpret_block(state, Scope::new(), body.clone())? {
+				ControlFlow::Normal => {},
+				ControlFlow::Continue(target)
+					if ControlFlow::targets(target.as_ref(), label.as_ref()) => {},
+				ControlFlow::Break(target, _)
+					if ControlFlow::targets(target.as_ref(), label.as_ref()) =>
+				{
+					break
+				},
+				c => return Ok(c),
+			}
+		},
+		StatementKind::For {
+			label,
+			pattern,
 			iterator,
 			body,
-		} => todo!(),
+		} => {
+			let mut iteration = Iteration::new(evaluate_expression(state, iterator)?)?;
+			while let Some(item) = {
+				state.line_number = line_number;
+				iteration.next(state)?
+			} {
+				let mut bindings = Vec::new();
+				destructure(&pattern, &item, &mut bindings)?;
+				let mut scope = Scope::new();
+				scope.variables.extend(bindings);
+				match interpret_block(state, scope, body.clone())? {
+					ControlFlow::Normal => {},
+					ControlFlow::Continue(target)
+						if ControlFlow::targets(target.as_ref(), label.as_ref()) => {},
+					ControlFlow::Break(target, _)
+						if ControlFlow::targets(target.as_ref(), label.as_ref()) =>
+					{
+						break
+					},
+					c => return Ok(c),
+				}
+			}
+		},
+		StatementKind::Throw(expression) => {
+			return Err(InterpreterErrorKind::Thrown(evaluate_expression(
+				state, expression,
+			)?))
+		},
+		StatementKind::Try {
+			body,
+			catch,
+			finally,
+		} => {
+			let depth = state.stack.len();
+			let module = state.module;
+			// Calls in `return` are made here, so their errors are caught and `finally` runs after them
+			let result = match (
+				interpret_block(state, Scope::new(), body)
+					.and_then(|control_flow| finish_tail_call(state, control_flow)),
+				catch,
+			) {
+				// Limits stop the script, so they can't be caught
+				(Err(error), Some((name, body))) if !error.is_limit() => {
+					// Scopes of the statements that failed are never popped
+					state.stack.truncate(depth);
+					let mut scope = Scope::new();
+					scope
+						.variables
+						.insert(name, error_value(error, state.line_number));
+					// The error may have come from a function of another module
+					state.module = module;
+					interpret_block(state, scope, body)
+						.and_then(|control_flow| finish_tail_call(state, control_flow))
+				},
+				(result, _) => result,
+			};
+			// Also drops the scopes left by a tail call that was made above
+			state.stack.truncate(depth);
+			if let Some(finally) =
+				finally.filter(|_| !matches!(&result, Err(error) if error.is_limit()))
+			{
+				let error_location = (state.line_number, state.module);
+				state.module = module;
+				match interpret_block(state, Scope::new(), finally)? {
+					ControlFlow::Normal => {},
+					c => return Ok(c),
+				}
+				(state.line_number, state.module) = error_location;
+			}
+			return result;
+		},
+		StatementKind::Import { path, name } => {
+			let exports = import(state, &path)?;
+			// Importers can't change what a module exports
+			declare(state, name, exports, true)?;
+		},
+		StatementKind::ImportNames { path, names } => {
+			let Value::Map(exports) = import(state, &path)? else {
+				unreachable!("exports are always a map");
+			};
+			for name in names {
+				let Some(value) = exports.get(&HashableValue::String(name.0.clone())) else {
+					return Err(InterpreterErrorKind::NotExported(name));
+				};
+				declare(state, name, value.clone(), true)?;
+			}
+		},
 	}
 	Ok(ControlFlow::Normal)
 }
-fn evaluate_expression(
+fn declare(
+	state: &mut State,
+	name: Identifier,
+	value: Value,
+	constant: bool,
+) -> Result<(), InterpreterErrorKind> {
+	let scope = state.current_scope();
+	if scope.variables.contains_key(&name) {
+		return Err(InterpreterErrorKind::Redeclaration);
+	}
+	if constant {
+		scope.constants.insert(name.clone());
+	}
+	scope.variables.insert(name, value);
+	Ok(())
+}
+/// Evaluates the module at the path the first time it's imported, returning the map of its exports
+fn import(state: &mut State, path: &str) -> Result<Value, InterpreterErrorKind> {
+	if let Some(name) = path.strip_prefix(stdlib::PREFIX) {
+		return stdlib::module(name)
+			.ok_or_else(|| InterpreterErrorKind::ModuleNotFound(path.to_owned()));
+	}
+	let importer = state.modules[state.module].name.as_deref().map(Path::new);
+	// Modules are files like any other, so they need the same capability as `fs.read_text`.
+	// Only readable files are looked for, so scripts can't learn whether others exist.
+	let candidates = module::candidates(path, importer);
+	let mut readable = candidates
+		.iter()
+		.filter(|candidate| state.config.capabilities.can_read(candidate))
+		.peekable();
+	if readable.peek().is_none() {
+		return Err(InterpreterErrorKind::PermissionDenied(format!(
+			"read '{}'",
+			candidates[0].display()
+		)));
+	}
+	let Some(found) = readable.find(|candidate| candidate.is_file()).cloned() else {
+		return Err(InterpreterErrorKind::ModuleNotFound(path.to_owned()));
+	};
+	let canonical = found
+		.canonicalize()
+		.map_err(|e| InterpreterErrorKind::Io(e.to_string()))?;
+
+	if let Some(id) = state
+		.modules
+		.iter()
+		.position(|module| module.path.as_ref() == Some(&canonical))
+	{
+		if let Some(exports) = &state.modules[id].exports {
+			return Ok(exports.clone());
+		}
+		// Modules being evaluated always import each other in order
+		let mut chain = state.modules[id..]
+			.iter()
+			.filter(|module| module.path.is_some() && module.exports.is_none())
+			.map(|module| module.name.clone().unwrap_or_default())
+			.collect::<Vec<_>>();
+		chain.push(found.display().to_string());
+		return Err(InterpreterErrorKind::ImportCycle(chain));
+	}
+
+	let name = found.display().to_string();
+	let source = fs::read_to_string(&found).map_err(|e| InterpreterErrorKind::Io(e.to_string()))?;
+	let ast = parser::parse_module(source, name.clone())
+		.map_err(InterpreterErrorKind::ModuleParseError)?;
+
+	let id = state.modules.len();
+	state.modules.push(Module::new(Some(name), Some(canonical)));
+
+	// Modules don't see the scopes of the importer
+	let stack = std::mem::replace(&mut state.stack, vec![builtins::scope(), Scope::new()]);
+	let line_number = state.line_number;
+	let importer = std::mem::replace(&mut state.module, id);
+	let result = interpret_statements(state, ast.statements);
+	let mut module_stack = std::mem::replace(&mut state.stack, stack);
+	let error = match result {
+		Ok(ControlFlow::Normal) => None,
+		Ok(_) => Some(InterpreterErrorKind::UpwardControlFlowReachedTopLevel),
+		Err(error) => Some(error),
+	};
+	if let Some(error) = error {
+		state.modules[id].path = None;
+		return Err(error);
+	}
+	state.module = importer;
+	state.line_number = line_number;
+
+	let globals = module_stack.pop().unwrap();
+	let exports = Value::Map(
+		ast.exports
+			.into_iter()
+			.map(|name| {
+				let value = globals.variables.get(&name).cloned().unwrap_or(Value::Null);
+				(HashableValue::String(name.0), value)
+			})
+			.collect(),
+	);
+	state.modules[id].globals = Some(globals);
+	state.modules[id].exports = Some(exports.clone());
+	Ok(exports)
+}
+/// Converts an error into the value bound by `catch`
+pub fn error_value(error: InterpreterErrorKind, line_number: LineNumber) -> Value {
+	if let InterpreterErrorKind::Thrown(value) = error {
+		return value;
+	}
+	let line_number = Integer::try_from(line_number).unwrap_or(Integer::MAX);
+	Value::Map(HashMap::from([
+		(
+			HashableValue::String("kind".to_owned()),
+			Value::String(error.name().to_owned()),
+		),
+		(
+			HashableValue::String("message".to_owned()),
+			Value::String(error.to_string()),
+		),
+		(
+			HashableValue::String("line".to_owned()),
+			Value::Integer(line_number),
+		),
+	]))
+}
+pub fn evaluate_expression(
+	state: &mut State,
+	expression: Expression,
+) -> Result<Value, InterpreterErrorKind> {
+	stack::grow(|| compute_expression(state, expression))
+}
+fn compute_expression(
 	state: &mut State,
 	expression: Expression,
-) -> Result<Value, InterpreterError> {
+) -> Result<Value, InterpreterErrorKind> {
 	Ok(match expression {
 		Expression::True => Value::Bool(true),
 		Expression::False => Value::Bool(false),
 		Expression::Null => Value::Null,
 
-		Expression::Identifier(identifier) => lookup(state, &identifier)?.clone(),
+		Expression::Identifier(identifier) => copied(state, lookup(state, &identifier)?.clone())?,
 		Expression::Integer(integer) => Value::Integer(integer),
 		Expression::Float(float) => Value::Float(float),
-		Expression::String(string) => Value::String(string),
-		Expression::Array(initializer) => Value::Array(
-			initializer
-				.into_iter()
-				.map(|e| evaluate_expression(state, e))
-				.collect::<Result<_, _>>()?,
-		),
+		Expression::String(string) => {
+			state.allocate(string.len())?;
+			Value::String(string)
+		},
+		Expression::Array(initializer) => {
+			Value::Array(evaluate_array_elements(state, initializer)?)
+		},
 		Expression::Map(initializer) => {
 			let mut map = HashMap::new();
-			for (key, value) in initializer {
+			for entry in initializer {
+				let (key, value) = match entry {
+					MapEntry::Pair(key, value) => (key, value),
+					MapEntry::Spread(other) => {
+						let Value::Map(other) = evaluate_expression(state, other)? else {
+							return Err(InterpreterErrorKind::ExpectedMap);
+						};
+						state.allocate(other.keys().map(HashableValue::entry_size).sum())?;
+						map.extend(other);
+						continue;
+					},
+				};
 				let key = evaluate_expression(state, key)?;
 				let value = evaluate_expression(state, value)?;
 
 				let Some(hashable_key) = key.try_as_hashable() else {
-					return Err(InterpreterError::MapKeyNotHashable);
+					return Err(InterpreterErrorKind::MapKeyNotHashable);
 				};
 
+				state.allocate(hashable_key.entry_size())?;
 				map.insert(hashable_key, value);
 			}
 			Value::Map(map)
 		},
 
-		Expression::Function(parameters, body) => Value::Function(parameters, body),
-		Expression::Call(function, mut arguments) => {
-			let function = evaluate_expression(state, *function)?;
-			let Value::Function(mut parameters, body) = function else {
-				return Err(InterpreterError::ExpectedFunction);
-			};
-			if arguments.len() != parameters.len() {
-				return Err(InterpreterError::WrongArgumentCount);
-			}
-			state.push();
-			for i in 0..arguments.len() {
-				let value = evaluate_expression(state, arguments.remove(0))?;
-				state
-					.current_scope()
-					.variables
-					.insert(parameters.remove(0), value);
-			}
-			let mut return_value = Value::Null;
-			for statement in body {
-				match interpret_statement(state, statement)? {
-					ControlFlow::Normal => {},
-					ControlFlow::Continue | ControlFlow::Break => {
-						return Err(InterpreterError::UpwardControlFlowReachedTopLevel)
-					},
-					ControlFlow::Return(value) => return_value = value,
-				}
+		Expression::Function(parameters, body) => Value::Function(parameters, body, state.module),
+		Expression::Call(function, arguments) => {
+			let Expression::Member(receiver, method) = *function else {
+				let function = evaluate_expression(state, *function)?;
+				let (positional, keyword) = evaluate_arguments(state, arguments)?;
+				return Ok(call(state, function, None, positional, keyword)?.0);
+			};
+
+			// A receiver stored in a variable gets the method's changes to `self` written back,
+			// unless it's a constant, in which case `self` is a constant too
+			let constant = receiver
+				.is_place()
+				.then(|| place_variable(&receiver))
+				.filter(|variable| is_constant(state, variable))
+				.cloned();
+			let (place, receiver) = if receiver.is_place() && constant.is_none() {
+				let (variable, path) = evaluate_place(state, *receiver)?;
+				let value = place_mut(state, &variable, path.clone(), false)?.clone();
+				(Some((variable, path)), copied(state, value)?)
+			} else {
+				(None, evaluate_expression(state, *receiver)?)
+			};
+			let (positional, keyword) = evaluate_arguments(state, arguments)?;
+			let (return_value, receiver) = match receiver {
+				// Generators follow the iteration protocol
+				Value::Generator(generator) if method.0 == "next" => {
+					if !positional.is_empty() || !keyword.is_empty() {
+						return Err(InterpreterErrorKind::WrongArgumentCount);
+					}
+					if let Some(variable) = constant {
+						return Err(InterpreterErrorKind::AssignmentToConstant(variable));
+					}
+					// Its progress is shared, so there's nothing to write back
+					(generator.resume(state)?.unwrap_or(Value::Null), None)
+				},
+				value => {
+					let function = get_member(&value, &method)?;
+					let receiver = Receiver {
+						value,
+						constant: constant.is_some(),
+					};
+					call(state, function, Some(receiver), positional, keyword)?
+				},
+			};
+			if let (Some((variable, path)), Some(receiver)) = (place, receiver) {
+				*place_mut(state, &variable, path, false)? = receiver;
 			}
-			state.pop();
 			return_value
 		},
 		Expression::Member(value, member) => {
-			let expression = Expression::BinaryOperation(
-				Box::new([*value, Expression::String(member.0)]),
-				BinaryOperation::Index,
-			);
-			evaluate_expression(state, expression)?
+			let optional = matches!(*value, Expression::NullSafe(_));
+			let value = evaluate_expression(state, *value)?;
+			copied(
+				state,
+				optional_access(optional, get_member(&value, &member))?,
+			)?
+		},
+		Expression::NullSafe(value) => match evaluate_expression(state, *value)? {
+			Value::Null => return Err(InterpreterErrorKind::ShortCircuit),
+			value => value,
+		},
+		Expression::OptionalChain(chain) => match evaluate_expression(state, *chain) {
+			Err(InterpreterErrorKind::ShortCircuit) => Value::Null,
+			result => result?,
+		},
+		Expression::Range {
+			bounds,
+			step,
+			inclusive,
+		} => {
+			let [start, end] = *bounds;
+			let start = evaluate_expression(state, start)?;
+			let end = evaluate_expression(state, end)?;
+			let step = match step {
+				Some(step) => evaluate_expression(state, *step)?,
+				None => Value::Integer(1),
+			};
+			let (Value::Integer(start), Value::Integer(end), Value::Integer(step)) =
+				(start, end, step)
+			else {
+				return Err(InterpreterErrorKind::UnsupportedOperation);
+			};
+			if step == 0 {
+				return Err(InterpreterErrorKind::InvalidRangeStep);
+			}
+			Value::Range(Range {
+				start,
+				end,
+				step,
+				inclusive,
+			})
+		},
+		Expression::If { branches } => {
+			for (condition, body) in branches {
+				let Value::Bool(condition) = evaluate_expression(state, condition)? else {
+					return Err(InterpreterErrorKind::ExpectedBool);
+				};
+				if condition {
+					return evaluate_block(state, body);
+				}
+			}
+			Value::Null
+		},
+		Expression::Block(body) => evaluate_block(state, body)?,
+		Expression::Loop { label, body } => loop {
+			state.step()?;
+			match interpret_block(state, Scope::new(), body.clone())? {
+				ControlFlow::Normal => {},
+				ControlFlow::Continue(target)
+					if ControlFlow::targets(target.as_ref(), label.as_ref()) => {},
+				ControlFlow::Break(target, value)
+					if ControlFlow::targets(target.as_ref(), label.as_ref()) =>
+				{
+					break value
+				},
+				// Carried out of the expression to the statement around it
+				c => return Err(InterpreterErrorKind::ControlFlow(Box::new(c))),
+			}
+		},
+		Expression::Match(scrutinee, arms) => {
+			let value = evaluate_expression(state, *scrutinee)?;
+			for arm in arms {
+				let mut bindings = Vec::new();
+				if !match_pattern(&arm.pattern, &value, &mut bindings) {
+					continue;
+				}
+				let mut scope = Scope::new();
+				scope.variables.extend(bindings);
+				state.stack.push(scope);
+				let result = evaluate_arm(state, arm.guard, arm.body);
+				if ends_scope(&result) {
+					state.pop();
+				}
+				if let Some(value) = result? {
+					return Ok(value);
+				}
+			}
+			return Err(InterpreterErrorKind::NoMatch);
+		},
+		Expression::Slice(value, start, end) => {
+			let value = evaluate_expression(state, *value)?;
+			let mut bound = |bound: Option<Box<Expression>>| match bound {
+				None => Ok(None),
+				Some(bound) => match evaluate_expression(state, *bound)? {
+					Value::Integer(i) => Ok(Some(i)),
+					_ => Err(InterpreterErrorKind::UnsupportedOperation),
+				},
+			};
+			let start = bound(start)?;
+			let end = bound(end)?;
+
+			let slice = match value {
+				Value::Array(array) => {
+					let range = slice_range(start, end, array.len());
+					Value::Array(array[range].to_vec())
+				},
+				Value::String(string) => {
+					let range = slice_range(start, end, string.chars().count());
+					Value::String(string.chars().skip(range.start).take(range.len()).collect())
+				},
+				_ => return Err(InterpreterErrorKind::UnsupportedOperation),
+			};
+			copied(state, slice)?
 		},
 
 		Expression::UnaryOperation(operand, operation) => {
@@ -206,59 +619,850 @@ fn evaluate_expression(
 		},
 		Expression::BinaryOperation(operands, operation) => {
 			let [lhs, rhs] = *operands;
+			let optional =
+				operation == BinaryOperation::Index && matches!(lhs, Expression::NullSafe(_));
 			let lhs = evaluate_expression(state, lhs)?;
+			// The right operand isn't evaluated if the left one decides the result
+			let decided = match operation {
+				BinaryOperation::And => matches!(lhs, Value::Bool(false)),
+				BinaryOperation::Or => matches!(lhs, Value::Bool(true)),
+				BinaryOperation::Coalesce => !matches!(lhs, Value::Null),
+				_ => false,
+			};
+			if decided {
+				return Ok(lhs);
+			}
 			let rhs = evaluate_expression(state, rhs)?;
-			binary_operation(lhs, rhs, operation)?
+			state.allocate(allocation_size(&lhs, &rhs, operation))?;
+			let result = optional_access(optional, binary_operation(lhs, rhs, operation))?;
+			if operation == BinaryOperation::Index {
+				copied(state, result)?
+			} else {
+				result
+			}
 		},
 	})
 }
-fn lookup<'a>(state: &'a State, identifier: &Identifier) -> Result<&'a Value, InterpreterError> {
+/// Counts a copy of part of an existing value against the allocation limit
+fn copied(state: &mut State, value: Value) -> Result<Value, InterpreterErrorKind> {
+	state.allocate(value.heap_size())?;
+	Ok(value)
+}
+/// Ends the optional chain for a member or index that's missing right after a `?.`,
+/// so it gives `null` as it would for a `null` receiver
+fn optional_access(
+	optional: bool,
+	result: Result<Value, InterpreterErrorKind>,
+) -> Result<Value, InterpreterErrorKind> {
+	match result {
+		Err(InterpreterErrorKind::KeyNotInMap | InterpreterErrorKind::IndexOutOfBounds { .. })
+			if optional =>
+		{
+			Err(InterpreterErrorKind::ShortCircuit)
+		},
+		result => result,
+	}
+}
+/// Runs statements in the given scope, stopping at the first non-normal control flow
+fn interpret_block(
+	state: &mut State,
+	scope: Scope,
+	body: Vec<Statement>,
+) -> Result<ControlFlow, InterpreterErrorKind> {
+	state.stack.push(scope);
+	let control_flow = interpret_statements(state, body)?;
+	// The function being called in the tail call can still see the variables of the block
+	if !matches!(control_flow, ControlFlow::TailCall { .. }) {
+		state.pop();
+	}
+	Ok(control_flow)
+}
+/// Runs statements in a new scope, giving the value of the last one
+fn evaluate_block(
+	state: &mut State,
+	mut body: Vec<Statement>,
+) -> Result<Value, InterpreterErrorKind> {
+	let line_number = state.line_number;
+	state.push();
+	let last = body.pop();
+	let result = match interpret_statements(state, body)? {
+		ControlFlow::Normal => last.map_or(Ok(Value::Null), |last| evaluate_statement(state, last)),
+		c => Err(InterpreterErrorKind::ControlFlow(Box::new(c))),
+	};
+	if ends_scope(&result) {
+		state.pop();
+		state.line_number = line_number;
+	}
+	result
+}
+/// Whether an expression that pushed a scope pops it after giving the result.
+/// Control flow is turned back into a result by a statement outside, so the scope must go,
+/// unless it's a tail call, which can still see the variables of the scope.
+fn ends_scope<T>(result: &Result<T, InterpreterErrorKind>) -> bool {
+	match result {
+		Ok(_) => true,
+		Err(InterpreterErrorKind::ControlFlow(control_flow)) => {
+			!matches!(**control_flow, ControlFlow::TailCall { .. })
+		},
+		Err(_) => false,
+	}
+}
+/// Runs the guard and body of a match arm whose pattern matched, giving `None` if the guard fails
+fn evaluate_arm(
+	state: &mut State,
+	guard: Option<Expression>,
+	body: Expression,
+) -> Result<Option<Value>, InterpreterErrorKind> {
+	if let Some(guard) = guard {
+		let Value::Bool(guard) = evaluate_expression(state, guard)? else {
+			return Err(InterpreterErrorKind::ExpectedBool);
+		};
+		if !guard {
+			return Ok(None);
+		}
+	}
+	evaluate_expression(state, body).map(Some)
+}
+/// Runs a statement, giving its value if it's an expression, `if`, block or `loop`
+fn evaluate_statement(
+	state: &mut State,
+	statement: Statement,
+) -> Result<Value, InterpreterErrorKind> {
+	state.line_number = statement.line_number;
+	let expression = match statement.kind {
+		StatementKind::UnusedExpression(expression) => expression,
+		StatementKind::If { branches } => Expression::If { branches },
+		StatementKind::Block { body } => Expression::Block(body),
+		StatementKind::Loop { label, body } => Expression::Loop { label, body },
+		_ => {
+			return match interpret_statement(state, statement)? {
+				ControlFlow::Normal => Ok(Value::Null),
+				c => Err(InterpreterErrorKind::ControlFlow(Box::new(c))),
+			}
+		},
+	};
+	evaluate_expression(state, expression)
+}
+/// Runs the statements in the current scope, stopping at the first that isn't `ControlFlow::Normal`
+fn interpret_statements(
+	state: &mut State,
+	body: Vec<Statement>,
+) -> Result<ControlFlow, InterpreterErrorKind> {
+	for statement in body {
+		match interpret_statement(state, statement)? {
+			ControlFlow::Normal => {},
+			c => return Ok(c),
+		}
+	}
+	Ok(ControlFlow::Normal)
+}
+/// Evaluates array literal elements or call arguments, expanding spreads
+fn evaluate_array_elements(
+	state: &mut State,
+	elements: Vec<ArrayElement>,
+) -> Result<Vec<Value>, InterpreterErrorKind> {
+	let mut values = Vec::with_capacity(elements.len());
+	for element in elements {
+		match element {
+			ArrayElement::Single(e) => {
+				let value = evaluate_expression(state, e)?;
+				state.allocate(size_of::<Value>())?;
+				values.push(value);
+			},
+			ArrayElement::Spread(e) => {
+				let mut iteration = Iteration::new(evaluate_expression(state, e)?)?;
+				while let Some(value) = iteration.next(state)? {
+					state.allocate(size_of::<Value>())?;
+					values.push(value);
+				}
+			},
+		}
+	}
+	Ok(values)
+}
+fn evaluate_arguments(
+	state: &mut State,
+	arguments: Arguments,
+) -> Result<(Vec<Value>, KeywordArguments), InterpreterErrorKind> {
+	let positional = evaluate_array_elements(state, arguments.positional)?;
+	let keyword = arguments
+		.keyword
+		.into_iter()
+		.map(|(name, e)| Ok((name, evaluate_expression(state, e)?)))
+		.collect::<Result<_, _>>()?;
+	Ok((positional, keyword))
+}
+pub fn call_function(
+	state: &mut State,
+	function: Value,
+	arguments: Vec<Value>,
+) -> Result<Value, InterpreterErrorKind> {
+	Ok(call(state, function, None, arguments, Vec::new())?.0)
+}
+/// The value a method is called on
+struct Receiver {
+	value: Value,
+	/// Whether the value is stored in a constant, so the method can't change it
+	constant: bool,
+}
+/// Calls the function with `self` bound to the receiver, if any.
+/// Returns the final value of `self` alongside the return value.
+fn call(
+	state: &mut State,
+	function: Value,
+	receiver: Option<Receiver>,
+	arguments: Vec<Value>,
+	keyword_arguments: KeywordArguments,
+) -> Result<(Value, Option<Value>), InterpreterErrorKind> {
+	match function {
+		Value::Function(parameters, body, module) => nested_call(state, |state| {
+			let has_receiver = receiver.is_some();
+			let (mut control_flow, mut scope) = in_module(state, module, |state| {
+				call_function_body(
+					state,
+					parameters,
+					body,
+					receiver,
+					arguments,
+					keyword_arguments,
+				)
+			})?;
+			let receiver = if has_receiver {
+				scope.variables.remove(&Identifier("self".to_owned()))
+			} else {
+				None
+			};
+			// Tail calls are made here, after the function making them has returned,
+			// so a chain of them doesn't grow the stack. Its variables stay in a scope
+			// below the callee's, so the callee sees them as it would in a normal call.
+			loop {
+				control_flow = match control_flow {
+					ControlFlow::Return(value) => return Ok((value, receiver)),
+					ControlFlow::TailCall {
+						function: Value::Function(parameters, body, module),
+						arguments,
+						keyword_arguments,
+					} => {
+						state.stack.push(scope);
+						let (control_flow, callee_scope) = in_module(state, module, |state| {
+							call_function_body(
+								state,
+								parameters,
+								body,
+								None,
+								arguments,
+								keyword_arguments,
+							)
+						})?;
+						scope = state.stack.pop().unwrap();
+						scope.merge(callee_scope);
+						control_flow
+					},
+					ControlFlow::TailCall {
+						function,
+						arguments,
+						keyword_arguments,
+					} => {
+						state.stack.push(scope);
+						let (value, _) = call(state, function, None, arguments, keyword_arguments)?;
+						state.pop();
+						return Ok((value, receiver));
+					},
+					_ => unreachable!("function bodies only return or make tail calls"),
+				};
+			}
+		}),
+		Value::NativeFunction(function) => {
+			if let Some((name, _)) = keyword_arguments.into_iter().next() {
+				return Err(InterpreterErrorKind::UnknownKeywordArgument(name));
+			}
+			Ok(((function.function)(state, arguments)?, None))
+		},
+		_ => Err(InterpreterErrorKind::ExpectedFunction),
+	}
+}
+/// Counts a call of a script function towards `Config::max_call_depth` while it runs
+pub fn nested_call<T>(
+	state: &mut State,
+	run: impl FnOnce(&mut State) -> Result<T, InterpreterErrorKind>,
+) -> Result<T, InterpreterErrorKind> {
+	if state.call_depth >= state.config.max_call_depth {
+		return Err(InterpreterErrorKind::StackOverflow);
+	}
+	state.step()?;
+	state.call_depth += 1;
+	let result = run(state);
+	state.call_depth -= 1;
+	result
+}
+/// Makes the call of a `ControlFlow::TailCall` right away, for code that can't let it wait
+pub fn finish_tail_call(
+	state: &mut State,
+	control_flow: ControlFlow,
+) -> Result<ControlFlow, InterpreterErrorKind> {
+	Ok(match control_flow {
+		ControlFlow::TailCall {
+			function,
+			arguments,
+			keyword_arguments,
+		} => ControlFlow::Return(call(state, function, None, arguments, keyword_arguments)?.0),
+		control_flow => control_flow,
+	})
+}
+/// Runs code of the module with the module's globals on the stack,
+/// unless one of its functions is already running and they're on the stack
+pub fn in_module<T>(
+	state: &mut State,
+	module: ModuleId,
+	run: impl FnOnce(&mut State) -> Result<T, InterpreterErrorKind>,
+) -> Result<T, InterpreterErrorKind> {
+	let depth = state.stack.len();
+	let globals = state.modules[module].globals.take();
+	let has_globals = globals.is_some();
+	if let Some(globals) = globals {
+		state.stack.push(globals);
+	}
+	let caller_module = std::mem::replace(&mut state.module, module);
+	let result = run(state);
+	if has_globals {
+		// Also drops any scopes left by an error
+		let globals = state.stack.drain(depth..).next().unwrap();
+		state.modules[module].globals = Some(globals);
+	}
+	if result.is_ok() {
+		state.module = caller_module;
+	}
+	result
+}
+/// Runs a call of a script function, which ends with a return or a tail call.
+/// Also gives the variables of the call, with those of the blocks a tail call was made in.
+fn call_function_body(
+	state: &mut State,
+	parameters: Parameters,
+	body: Vec<Statement>,
+	receiver: Option<Receiver>,
+	arguments: Vec<Value>,
+	keyword_arguments: KeywordArguments,
+) -> Result<(ControlFlow, Scope), InterpreterErrorKind> {
+	let self_identifier = Identifier("self".to_owned());
+	let mut scope = Scope::new();
+	let has_receiver = receiver.is_some();
+	if let Some(receiver) = receiver {
+		if receiver.constant {
+			scope.constants.insert(self_identifier.clone());
+		}
+		scope.variables.insert(self_identifier, receiver.value);
+	}
+	let base = state.stack.len();
+	state.stack.push(scope);
+	// Defaults are evaluated in the function's scope, so they can refer to earlier parameters
+	bind_arguments(state, parameters, arguments, keyword_arguments)?;
+	if body.iter().any(Statement::contains_yield) {
+		let scope = state.stack.pop().unwrap();
+		let generator = Generator::new(scope, body, state.module);
+		return Ok((
+			ControlFlow::Return(Value::Generator(generator)),
+			Scope::new(),
+		));
+	}
+	let line_number = state.line_number;
+	let mut control_flow = match interpret_statements(state, body)? {
+		ControlFlow::Normal => ControlFlow::Return(Value::Null),
+		ControlFlow::Break(..) | ControlFlow::Continue(_) => {
+			return Err(InterpreterErrorKind::LoopControlFlowReachedFunction)
+		},
+		control_flow => control_flow,
+	};
+	// `self` is written back once the method returns, so it can't wait for a tail call
+	if has_receiver {
+		control_flow = finish_tail_call(state, control_flow)?;
+	}
+	let scope = state
+		.stack
+		.split_off(base)
+		.into_iter()
+		.reduce(|mut scope, block| {
+			scope.merge(block);
+			scope
+		})
+		.unwrap();
+	state.line_number = line_number;
+	Ok((control_flow, scope))
+}
+/// Binds the arguments of a call into the current scope
+fn bind_arguments(
+	state: &mut State,
+	parameters: Parameters,
+	arguments: Vec<Value>,
+	mut keyword_arguments: KeywordArguments,
+) -> Result<(), InterpreterErrorKind> {
+	for (i, (name, _)) in keyword_arguments.iter().enumerate() {
+		if keyword_arguments[..i]
+			.iter()
+			.any(|(other, _)| other == name)
+		{
+			return Err(InterpreterErrorKind::DuplicateArgument(name.clone()));
+		}
+	}
+	// Checked first, since a misspelled keyword would otherwise show up as a missing argument
+	let unknown = keyword_arguments.iter().find(|(name, _)| {
+		!parameters
+			.positional
+			.iter()
+			.any(|parameter| matches!(&parameter.pattern, Pattern::Binding(other) if other == name))
+	});
+	if let Some((name, _)) = unknown {
+		return Err(InterpreterErrorKind::UnknownKeywordArgument(name.clone()));
+	}
+
+	let mut arguments = arguments.into_iter();
+	for parameter in parameters.positional {
+		// Only parameters that bind a plain name can be passed by keyword
+		let name = match &parameter.pattern {
+			Pattern::Binding(name) => Some(name),
+			_ => None,
+		};
+		let keyword = name
+			.and_then(|name| {
+				keyword_arguments
+					.iter()
+					.position(|(other, _)| other == name)
+			})
+			.map(|i| keyword_arguments.remove(i).1);
+		let value = match (arguments.next(), keyword) {
+			(Some(_), Some(_)) => {
+				return Err(InterpreterErrorKind::DuplicateArgument(
+					name.unwrap().clone(),
+				))
+			},
+			(Some(value), None) | (None, Some(value)) => value,
+			(None, None) => match parameter.default {
+				Some(default) => evaluate_expression(state, default)?,
+				None => return Err(InterpreterErrorKind::WrongArgumentCount),
+			},
+		};
+		let mut bindings = Vec::new();
+		destructure(&parameter.pattern, &value, &mut bindings)?;
+		state.current_scope().variables.extend(bindings);
+	}
+
+	let remaining = arguments.collect::<Vec<_>>();
+	match parameters.rest {
+		Some(rest) => {
+			state
+				.current_scope()
+				.variables
+				.insert(rest, Value::Array(remaining));
+		},
+		None if !remaining.is_empty() => return Err(InterpreterErrorKind::WrongArgumentCount),
+		None => {},
+	}
+	Ok(())
+}
+/// The state of a `for` loop over some value
+#[derive(Debug, Clone)]
+pub enum Iteration {
+	Values(std::vec::IntoIter<Value>),
+	Range(Range, usize),
+	/// A map with a `next` function, which returns `null` once exhausted
+	Protocol(Value),
+	Generator(Generator),
+}
+impl Iteration {
+	pub fn new(value: Value) -> Result<Self, InterpreterErrorKind> {
+		Ok(match value {
+			Value::Generator(generator) => Self::Generator(generator),
+			Value::Array(array) => Self::Values(array.into_iter()),
+			Value::String(string) => Self::Values(
+				string
+					.chars()
+					.map(|c| Value::String(c.to_string()))
+					.collect::<Vec<_>>()
+					.into_iter(),
+			),
+			Value::Range(range) => Self::Range(range, 0),
+			Value::Map(mut map) => {
+				let next = map.remove(&HashableValue::String("next".to_owned()));
+				next.map_or_else(
+					|| {
+						let mut keys = map.into_keys().collect::<Vec<_>>();
+						keys.sort();
+						Self::Values(
+							keys.into_iter()
+								.map(Value::from)
+								.collect::<Vec<_>>()
+								.into_iter(),
+						)
+					},
+					Self::Protocol,
+				)
+			},
+			_ => return Err(InterpreterErrorKind::NotIterable),
+		})
+	}
+
+	pub fn next(&mut self, state: &mut State) -> Result<Option<Value>, InterpreterErrorKind> {
+		state.step()?;
+		Ok(match self {
+			Self::Values(values) => values.next(),
+			Self::Range(range, index) => {
+				let item = range.get(*index).map(Value::Integer);
+				*index += 1;
+				item
+			},
+			Self::Protocol(next) => match call_function(state, next.clone(), Vec::new())? {
+				Value::Null => None,
+				item => Some(item),
+			},
+			Self::Generator(generator) => generator.resume(state)?,
+		})
+	}
+}
+/// Gets a member of a value, falling back to the prototype of maps for missing keys
+fn get_member(value: &Value, member: &Identifier) -> Result<Value, InterpreterErrorKind> {
+	let Value::Map(map) = value else {
+		return binary_operation(
+			value.clone(),
+			Value::String(member.0.clone()),
+			BinaryOperation::Index,
+		);
+	};
+	let key = HashableValue::String(member.0.clone());
+	let mut map = map;
+	loop {
+		if let Some(value) = map.get(&key) {
+			return Ok(value.clone());
+		}
+		match map.get(&HashableValue::String(PROTOTYPE_KEY.to_owned())) {
+			Some(Value::Map(prototype)) => map = prototype,
+			_ => return Err(InterpreterErrorKind::KeyNotInMap),
+		}
+	}
+}
+fn lookup<'a>(
+	state: &'a State,
+	identifier: &Identifier,
+) -> Result<&'a Value, InterpreterErrorKind> {
 	for scope in state.stack.iter().rev() {
 		if let Some(value) = scope.variables.get(identifier) {
 			return Ok(value);
 		}
 	}
-	Err(InterpreterError::UnknownIdentifier)
+	Err(InterpreterErrorKind::UnknownIdentifier)
 }
 fn lookup_mut<'a>(
 	state: &'a mut State,
 	identifier: &Identifier,
-) -> Result<&'a mut Value, InterpreterError> {
+) -> Result<&'a mut Value, InterpreterErrorKind> {
 	for scope in state.stack.iter_mut().rev() {
 		if let Some(value) = scope.variables.get_mut(identifier) {
+			if scope.constants.contains(identifier) {
+				return Err(InterpreterErrorKind::AssignmentToConstant(
+					identifier.clone(),
+				));
+			}
 			return Ok(value);
 		}
 	}
-	Err(InterpreterError::UnknownIdentifier)
+	Err(InterpreterErrorKind::UnknownIdentifier)
+}
+fn is_constant(state: &State, identifier: &Identifier) -> bool {
+	state
+		.stack
+		.iter()
+		.rev()
+		.find(|scope| scope.variables.contains_key(identifier))
+		.is_some_and(|scope| scope.constants.contains(identifier))
+}
+/// The variable a place expression refers to a part of
+fn place_variable(expression: &Expression) -> &Identifier {
+	match expression {
+		Expression::Identifier(identifier) => identifier,
+		Expression::Member(base, _) => place_variable(base),
+		Expression::BinaryOperation(operands, BinaryOperation::Index) => {
+			place_variable(&operands[0])
+		},
+		_ => unreachable!("places are checked with `Expression::is_place`"),
+	}
+}
+fn assign(state: &mut State, lvalue: Lvalue, value: Value) -> Result<(), InterpreterErrorKind> {
+	match lvalue {
+		Lvalue::Array(lvalues, rest) => {
+			let Value::Array(mut array) = value else {
+				return Err(InterpreterErrorKind::ExpectedArray);
+			};
+			let length_matches = match rest {
+				Some(_) => array.len() >= lvalues.len(),
+				None => array.len() == lvalues.len(),
+			};
+			if !length_matches {
+				return Err(InterpreterErrorKind::WrongArrayLength {
+					expected: lvalues.len(),
+					found: array.len(),
+				});
+			}
+			let remaining = array.split_off(lvalues.len());
+			for (lvalue, value) in lvalues.into_iter().zip(array) {
+				assign(state, lvalue, value)?;
+			}
+			if let Some(rest) = rest {
+				assign(state, *rest, Value::Array(remaining))?;
+			}
+		},
+		Lvalue::Map(entries) => {
+			let Value::Map(map) = value else {
+				return Err(InterpreterErrorKind::ExpectedMap);
+			};
+			for (key, lvalue) in entries {
+				let Some(key) = evaluate_expression(state, key)?.try_as_hashable() else {
+					return Err(InterpreterErrorKind::MapKeyNotHashable);
+				};
+				let Some(value) = map.get(&key) else {
+					return Err(InterpreterErrorKind::KeyNotInMap);
+				};
+				assign(state, lvalue, value.clone())?;
+			}
+		},
+		lvalue => {
+			let (variable, mut path) = evaluate_lvalue(state, lvalue)?;
+			// Assigning to a missing key adds an entry to the map
+			if let Some(key) = path.pop() {
+				let entry_size = match place_mut(state, &variable, path.clone(), false)? {
+					Value::Map(map) => key
+						.clone()
+						.try_as_hashable()
+						.filter(|key| !map.contains_key(key))
+						.map_or(0, |key| key.entry_size()),
+					_ => 0,
+				};
+				state.allocate(entry_size)?;
+				path.push(key);
+			}
+			*place_mut(state, &variable, path, true)? = value;
+		},
+	}
+	Ok(())
+}
+/// Evaluates the keys of an lvalue, returning the variable it's rooted in and the path from it
+fn evaluate_lvalue(
+	state: &mut State,
+	lvalue: Lvalue,
+) -> Result<(Identifier, Vec<Value>), InterpreterErrorKind> {
+	Ok(match lvalue {
+		Lvalue::Identifier(identifier) => (identifier, Vec::new()),
+		Lvalue::Member(base, member) => {
+			let (variable, mut path) = evaluate_place(state, *base)?;
+			path.push(Value::String(member.0));
+			(variable, path)
+		},
+		Lvalue::Index(operands) => {
+			let [base, key] = *operands;
+			let (variable, mut path) = evaluate_place(state, base)?;
+			path.push(evaluate_expression(state, key)?);
+			(variable, path)
+		},
+		Lvalue::Array(..) | Lvalue::Map(_) => unreachable!("destructuring is handled by `assign`"),
+	})
+}
+fn evaluate_place(
+	state: &mut State,
+	expression: Expression,
+) -> Result<(Identifier, Vec<Value>), InterpreterErrorKind> {
+	let lvalue = match expression {
+		Expression::Identifier(identifier) => Lvalue::Identifier(identifier),
+		Expression::Member(base, member) => Lvalue::Member(base, member),
+		Expression::BinaryOperation(operands, BinaryOperation::Index) => Lvalue::Index(operands),
+		_ => unreachable!("lvalues are checked by the parser"),
+	};
+	evaluate_lvalue(state, lvalue)
+}
+/// Follows a path produced by `evaluate_lvalue`.
+/// With `insert`, a missing final map key is created instead of being an error.
+fn place_mut<'a>(
+	state: &'a mut State,
+	variable: &Identifier,
+	path: Vec<Value>,
+	insert: bool,
+) -> Result<&'a mut Value, InterpreterErrorKind> {
+	let mut place = lookup_mut(state, variable)?;
+	let last = path.len().saturating_sub(1);
+	for (i, key) in path.into_iter().enumerate() {
+		place = match place {
+			Value::Map(map) => {
+				let Some(key) = key.try_as_hashable() else {
+					return Err(InterpreterErrorKind::MapKeyNotHashable);
+				};
+				if insert && i == last {
+					map.entry(key).or_insert(Value::Null)
+				} else {
+					match map.get_mut(&key) {
+						Some(value) => value,
+						None => return Err(InterpreterErrorKind::KeyNotInMap),
+					}
+				}
+			},
+			Value::Array(array) => {
+				let Value::Integer(index) = key else {
+					return Err(InterpreterErrorKind::UnsupportedOperation);
+				};
+				let length = array.len();
+				match normalize_index(index, length) {
+					Some(i) => &mut array[i],
+					None => return Err(InterpreterErrorKind::IndexOutOfBounds { index, length }),
+				}
+			},
+			_ => return Err(InterpreterErrorKind::UnsupportedOperation),
+		};
+	}
+	Ok(place)
+}
+/// Resolves a possibly negative index, returning `None` if it's out of bounds
+fn normalize_index(index: Integer, length: usize) -> Option<usize> {
+	let magnitude = index.unsigned_abs() as usize;
+	let index = if index < 0 {
+		length.checked_sub(magnitude)?
+	} else {
+		magnitude
+	};
+	(index < length).then_some(index)
 }
-fn unary_operation(operand: Value, operation: UnaryOperation) -> Result<Value, InterpreterError> {
+/// Collects the elements at each index of the range, which may be negative
+fn index_by_range<T: Clone>(values: &[T], range: Range) -> Result<Vec<T>, InterpreterErrorKind> {
+	(0..range.len())
+		.map(|i| {
+			let index = range.get(i).unwrap();
+			normalize_index(index, values.len())
+				.map(|i| values[i].clone())
+				.ok_or(InterpreterErrorKind::IndexOutOfBounds {
+					index,
+					length: values.len(),
+				})
+		})
+		.collect()
+}
+/// Resolves slice bounds, which may be negative and are clamped to the length
+fn slice_range(
+	start: Option<Integer>,
+	end: Option<Integer>,
+	length: usize,
+) -> std::ops::Range<usize> {
+	let clamp = |bound: Integer| {
+		let magnitude = bound.unsigned_abs() as usize;
+		if bound < 0 {
+			length.saturating_sub(magnitude)
+		} else {
+			magnitude.min(length)
+		}
+	};
+	let start = start.map_or(0, clamp);
+	let end = end.map_or(length, clamp).max(start);
+	start..end
+}
+fn unary_operation(
+	operand: Value,
+	operation: UnaryOperation,
+) -> Result<Value, InterpreterErrorKind> {
 	Ok(match (operand, operation) {
 		(Value::Bool(true), UnaryOperation::Not) => Value::Bool(false),
 		(Value::Bool(false), UnaryOperation::Not) => Value::Bool(true),
 
-		(Value::Integer(i), UnaryOperation::Neg) => Value::Integer(-i),
+		(Value::Integer(i), UnaryOperation::Neg) => Value::Integer(
+			i.checked_neg()
+				.ok_or(InterpreterErrorKind::IntegerOverflow)?,
+		),
 		(Value::Float(f), UnaryOperation::Neg) => Value::Float(-f),
 
-		_ => return Err(InterpreterError::UnsupportedOperation),
+		(Value::Integer(i), UnaryOperation::BitNot) => Value::Integer(!i),
+
+		_ => return Err(InterpreterErrorKind::UnsupportedOperation),
 	})
 }
+/// Bytes of the string, array or map an operation builds, counted before it's built
+fn allocation_size(lhs: &Value, rhs: &Value, operation: BinaryOperation) -> usize {
+	use BinaryOperation as O;
+	use Value as V;
+
+	match (lhs, rhs, operation) {
+		(V::String(_), V::String(_), O::Add)
+		| (V::Array(_), V::Array(_), O::Add)
+		| (V::Map(_), V::Map(_), O::Add) => lhs.heap_size() + rhs.heap_size(),
+		(value @ (V::String(_) | V::Array(_)), V::Integer(count), O::Mul)
+		| (V::Integer(count), value @ (V::String(_) | V::Array(_)), O::Mul) => value
+			.heap_size()
+			.saturating_mul(usize::try_from(*count).unwrap_or(0)),
+		_ => 0,
+	}
+}
 fn binary_operation(
 	lhs: Value,
 	rhs: Value,
 	operation: BinaryOperation,
-) -> Result<Value, InterpreterError> {
+) -> Result<Value, InterpreterErrorKind> {
 	use BinaryOperation as O;
 	use Value as V;
 
+	// Mixed integer/float operands are promoted to floats
+	let (lhs, rhs) = match (lhs, rhs) {
+		(V::Integer(lhs), V::Float(rhs)) => (V::Float(Float::from(lhs)), V::Float(rhs)),
+		(V::Float(lhs), V::Integer(rhs)) => (V::Float(lhs), V::Float(Float::from(rhs))),
+		operands => operands,
+	};
+
+	let overflow = || InterpreterErrorKind::IntegerOverflow;
 	Ok(match (lhs, rhs, operation) {
 		//
 		// Integer ops
 		//
-		(V::Integer(lhs), V::Integer(rhs), O::Add) => V::Integer(lhs + rhs),
-		(V::Integer(lhs), V::Integer(rhs), O::Sub) => V::Integer(lhs - rhs),
-		(V::Integer(lhs), V::Integer(rhs), O::Mul) => V::Integer(lhs * rhs),
-		(V::Integer(lhs), V::Integer(rhs), O::Div) => V::Integer(lhs / rhs),
-		(V::Integer(lhs), V::Integer(rhs), O::Mod) => V::Integer(lhs % rhs),
+		(V::Integer(lhs), V::Integer(rhs), O::Add) => {
+			V::Integer(lhs.checked_add(rhs).ok_or_else(overflow)?)
+		},
+		(V::Integer(lhs), V::Integer(rhs), O::Sub) => {
+			V::Integer(lhs.checked_sub(rhs).ok_or_else(overflow)?)
+		},
+		(V::Integer(lhs), V::Integer(rhs), O::Mul) => {
+			V::Integer(lhs.checked_mul(rhs).ok_or_else(overflow)?)
+		},
+		(V::Integer(_), V::Integer(0), O::Div | O::Mod | O::FloorDiv) => {
+			return Err(InterpreterErrorKind::DivisionByZero)
+		},
+		// Dividing the smallest integer by -1 is the only other way these can fail
+		(V::Integer(lhs), V::Integer(rhs), O::Div) => {
+			V::Integer(lhs.checked_div(rhs).ok_or_else(overflow)?)
+		},
+		(V::Integer(lhs), V::Integer(rhs), O::Mod) => {
+			V::Integer(lhs.checked_rem(rhs).ok_or_else(overflow)?)
+		},
+		(V::Integer(lhs), V::Integer(rhs), O::FloorDiv) => {
+			let quotient = lhs.checked_div(rhs).ok_or_else(overflow)?;
+			if lhs % rhs != 0 && (lhs < 0) != (rhs < 0) {
+				V::Integer(quotient - 1)
+			} else {
+				V::Integer(quotient)
+			}
+		},
+		(V::Integer(lhs), V::Integer(rhs), O::Pow) => match u32::try_from(rhs) {
+			Ok(exponent) => V::Integer(lhs.checked_pow(exponent).ok_or_else(overflow)?),
+			// Negative exponents can't produce an integer
+			Err(_) => V::Float(Float::from(lhs).powi(rhs)),
+		},
+
+		//
+		// Bitwise ops
+		//
+		(V::Integer(lhs), V::Integer(rhs), O::BitAnd) => V::Integer(lhs & rhs),
+		(V::Integer(lhs), V::Integer(rhs), O::BitOr) => V::Integer(lhs | rhs),
+		(V::Integer(lhs), V::Integer(rhs), O::BitXor) => V::Integer(lhs ^ rhs),
+		(V::Integer(lhs), V::Integer(rhs), O::Shl | O::Shr) => {
+			let shifted = u32::try_from(rhs).ok().and_then(|rhs| match operation {
+				O::Shl => lhs.checked_shl(rhs),
+				O::Shr => lhs.checked_shr(rhs),
+				_ => unreachable!(),
+			});
+			match shifted {
+				Some(i) => V::Integer(i),
+				None => return Err(InterpreterErrorKind::InvalidShiftAmount),
+			}
+		},
 
 		//
 		// Float ops
@@ -267,42 +1471,975 @@ fn binary_operation(
 		(V::Float(lhs), V::Float(rhs), O::Sub) => V::Float(lhs - rhs),
 		(V::Float(lhs), V::Float(rhs), O::Mul) => V::Float(lhs * rhs),
 		(V::Float(lhs), V::Float(rhs), O::Div) => V::Float(lhs / rhs),
+		(V::Float(lhs), V::Float(rhs), O::Mod) => V::Float(lhs % rhs),
+		(V::Float(lhs), V::Float(rhs), O::FloorDiv) => V::Float((lhs / rhs).floor()),
+		(V::Float(lhs), V::Float(rhs), O::Pow) => V::Float(lhs.powf(rhs)),
 
 		//
 		// Comparison ops
 		//
-		(V::Integer(lhs), V::Integer(rhs), O::Eq) => V::Bool(lhs == rhs),
-		(V::Integer(lhs), V::Integer(rhs), O::NoEq) => V::Bool(lhs != rhs),
-
-		(V::Integer(lhs), V::Integer(rhs), O::Lt) => V::Bool(lhs < rhs),
-		(V::Integer(lhs), V::Integer(rhs), O::LtEq) => V::Bool(lhs <= rhs),
-		(V::Integer(lhs), V::Integer(rhs), O::Gt) => V::Bool(lhs > rhs),
-		(V::Integer(lhs), V::Integer(rhs), O::GtEq) => V::Bool(lhs >= rhs),
+		(lhs, rhs, O::Eq) => V::Bool(lhs == rhs),
+		(lhs, rhs, O::NoEq) => V::Bool(lhs != rhs),
 
+		// Kept separate so NaN compares false instead of being unordered
 		(V::Float(lhs), V::Float(rhs), O::Lt) => V::Bool(lhs < rhs),
 		(V::Float(lhs), V::Float(rhs), O::LtEq) => V::Bool(lhs <= rhs),
 		(V::Float(lhs), V::Float(rhs), O::Gt) => V::Bool(lhs > rhs),
 		(V::Float(lhs), V::Float(rhs), O::GtEq) => V::Bool(lhs >= rhs),
 
+		(lhs, rhs, O::Lt | O::LtEq | O::Gt | O::GtEq) => {
+			let Some(ordering) = lhs.partial_cmp(&rhs) else {
+				return Err(InterpreterErrorKind::UnsupportedOperation);
+			};
+			V::Bool(match operation {
+				O::Lt => ordering.is_lt(),
+				O::LtEq => ordering.is_le(),
+				O::Gt => ordering.is_gt(),
+				O::GtEq => ordering.is_ge(),
+				_ => unreachable!(),
+			})
+		},
+
 		//
 		// Bool ops
 		//
 		(V::Bool(lhs), V::Bool(rhs), O::And) => V::Bool(lhs && rhs),
 		(V::Bool(lhs), V::Bool(rhs), O::Or) => V::Bool(lhs || rhs),
+		(V::Null, rhs, O::Coalesce) => rhs,
+		(lhs, _, O::Coalesce) => lhs,
+
+		//
+		// String, array and map ops
+		//
+		(V::String(lhs), V::String(rhs), O::Add) => V::String(lhs + &rhs),
+		(V::Array(mut lhs), V::Array(rhs), O::Add) => {
+			lhs.extend(rhs);
+			V::Array(lhs)
+		},
+		(V::Map(mut lhs), V::Map(rhs), O::Add) => {
+			lhs.extend(rhs);
+			V::Map(lhs)
+		},
+
+		(V::String(string), V::Integer(count), O::Mul)
+		| (V::Integer(count), V::String(string), O::Mul) => {
+			V::String(string.repeat(usize::try_from(count).unwrap_or(0)))
+		},
+		(V::Array(array), V::Integer(count), O::Mul)
+		| (V::Integer(count), V::Array(array), O::Mul) => {
+			let count = usize::try_from(count).unwrap_or(0);
+			V::Array(
+				array
+					.iter()
+					.cycle()
+					.take(array.len() * count)
+					.cloned()
+					.collect(),
+			)
+		},
+
+		(key, V::Map(map), O::In) => {
+			let Some(key) = key.try_as_hashable() else {
+				return Err(InterpreterErrorKind::MapKeyNotHashable);
+			};
+			V::Bool(map.contains_key(&key))
+		},
+		(value, V::Array(array), O::In) => V::Bool(array.contains(&value)),
+		(V::Integer(i), V::Range(range), O::In) => V::Bool(range.contains(i)),
+		(_, V::Range(_), O::In) => V::Bool(false),
+		(V::String(substring), V::String(string), O::In) => {
+			V::Bool(string.contains(substring.as_str()))
+		},
 
 		//
 		// Index
 		//
+		(V::Array(array), V::Integer(index), O::Index) => {
+			let length = array.len();
+			match normalize_index(index, length) {
+				Some(i) => array[i].clone(),
+				None => return Err(InterpreterErrorKind::IndexOutOfBounds { index, length }),
+			}
+		},
+		(V::String(string), V::Integer(index), O::Index) => {
+			let length = string.chars().count();
+			match normalize_index(index, length) {
+				Some(i) => V::String(string.chars().nth(i).unwrap().to_string()),
+				None => return Err(InterpreterErrorKind::IndexOutOfBounds { index, length }),
+			}
+		},
+		(V::Range(range), V::Integer(index), O::Index) => {
+			let length = range.len();
+			match normalize_index(index, length).and_then(|i| range.get(i)) {
+				Some(i) => V::Integer(i),
+				None => return Err(InterpreterErrorKind::IndexOutOfBounds { index, length }),
+			}
+		},
+		(V::Array(array), V::Range(range), O::Index) => V::Array(index_by_range(&array, range)?),
+		(V::String(string), V::Range(range), O::Index) => {
+			let chars = string.chars().collect::<Vec<_>>();
+			V::String(index_by_range(&chars, range)?.into_iter().collect())
+		},
 		(V::Map(map), key, O::Index) => {
 			let Some(key) = key.try_as_hashable() else {
-				return Err(InterpreterError::MapKeyNotHashable);
+				return Err(InterpreterErrorKind::MapKeyNotHashable);
 			};
 			match map.get(&key) {
 				Some(value) => value.clone(),
-				None => return Err(InterpreterError::KeyNotInMap),
+				None => return Err(InterpreterErrorKind::KeyNotInMap),
 			}
 		},
 
-		_ => return Err(InterpreterError::UnsupportedOperation),
+		_ => return Err(InterpreterErrorKind::UnsupportedOperation),
 	})
 }
+
+#[cfg(test)]
+pub mod tests {
+	use std::{path::PathBuf, time::Instant};
+
+	use super::*;
+	use crate::{
+		interpreter::config::{CancellationHandle, Capabilities},
+		parser::parse,
+	};
+
+	/// Runs the source with the config, giving the value of its `result` variable, or `null`
+	pub fn evaluate_with_config(
+		source: &str,
+		config: Config,
+	) -> Result<Value, InterpreterErrorKind> {
+		let mut state = State::new(config);
+		state.modules.push(Module::new(None, None));
+		state.stack.push(builtins::scope());
+		state.push();
+		match interpret_statements(&mut state, parse(source).unwrap().statements)? {
+			ControlFlow::Normal => {},
+			_ => return Err(InterpreterErrorKind::UpwardControlFlowReachedTopLevel),
+		}
+		Ok(lookup(&state, &Identifier("result".to_owned()))
+			.cloned()
+			.unwrap_or(Value::Null))
+	}
+
+	pub fn evaluate(source: &str) -> Result<Value, InterpreterErrorKind> {
+		evaluate_with_config(source, Config::default())
+	}
+
+	pub fn array(values: impl IntoIterator<Item = Value>) -> Value {
+		Value::Array(values.into_iter().collect())
+	}
+
+	pub fn integers(integers: impl IntoIterator<Item = Integer>) -> Value {
+		array(integers.into_iter().map(Value::Integer))
+	}
+
+	pub fn string(string: &str) -> Value { Value::String(string.to_owned()) }
+
+	#[test]
+	fn integer_arithmetic() {
+		assert_eq!(evaluate("let result = 7 // 2;").unwrap(), Value::Integer(3));
+		assert_eq!(
+			evaluate("let result = -7 // 2;").unwrap(),
+			Value::Integer(-4)
+		);
+		assert_eq!(
+			evaluate("let result = 7 // -2;").unwrap(),
+			Value::Integer(-4)
+		);
+		assert_eq!(
+			evaluate("let result = 2 ** 10;").unwrap(),
+			Value::Integer(1024)
+		);
+		assert_eq!(
+			evaluate("let result = 2 ** -1;").unwrap(),
+			Value::Float(0.5)
+		);
+		assert_eq!(
+			evaluate("let result = 1 + 0.5;").unwrap(),
+			Value::Float(1.5)
+		);
+		assert_eq!(
+			evaluate("let result = 1 == 1.0;").unwrap(),
+			Value::Bool(true)
+		);
+	}
+
+	#[test]
+	fn integer_errors() {
+		for source in ["7 // 0;", "7 / 0;", "7 % 0;"] {
+			assert!(matches!(
+				evaluate(source),
+				Err(InterpreterErrorKind::DivisionByZero)
+			));
+		}
+		for source in [
+			"2 ** 40;",
+			"2147483647 + 1;",
+			"-2147483647 - 2;",
+			"65536 * 65536;",
+		] {
+			assert!(matches!(
+				evaluate(source),
+				Err(InterpreterErrorKind::IntegerOverflow)
+			));
+		}
+	}
+
+	#[test]
+	fn equality_across_types() {
+		let source = r#"
+			let result = [
+				null == null, 1 == "1", [1, [2]] == [1, [2]], {"a": 1} == {"a": 1.0},
+				"a" != "b", [1] == [1, 2], null == false,
+			];
+		"#;
+		let expected = [true, false, true, true, true, false, false];
+		assert_eq!(evaluate(source).unwrap(), array(expected.map(Value::Bool)));
+	}
+
+	#[test]
+	fn ordering() {
+		let source = r#"let result = ["ab" < "b", [1, 2] < [1, 3], [1] < [1, 0], 2 > 1.5];"#;
+		assert_eq!(evaluate(source).unwrap(), array([true; 4].map(Value::Bool)));
+		assert!(matches!(
+			evaluate(r#"[1] < "a";"#),
+			Err(InterpreterErrorKind::UnsupportedOperation)
+		));
+	}
+
+	#[test]
+	fn sort_uses_the_total_order() {
+		let source = r#"let result = sort(["b", 2, null, [1], 1.5, true, "a"]);"#;
+		assert_eq!(
+			evaluate(source).unwrap(),
+			array([
+				Value::Null,
+				Value::Bool(true),
+				Value::Float(1.5),
+				Value::Integer(2),
+				string("a"),
+				string("b"),
+				array([Value::Integer(1)]),
+			])
+		);
+	}
+
+	#[test]
+	fn indexing_and_slicing() {
+		let source = r#"
+			let xs = [1, 2, 3, 4];
+			let s = "hello";
+			let result = [xs[0], xs[-1], s[1], xs[1:3], xs[:-1], s[2:], xs[3:1], xs[-10:10]];
+		"#;
+		assert_eq!(
+			evaluate(source).unwrap(),
+			array([
+				Value::Integer(1),
+				Value::Integer(4),
+				string("e"),
+				integers([2, 3]),
+				integers([1, 2, 3]),
+				string("llo"),
+				integers([]),
+				integers([1, 2, 3, 4]),
+			])
+		);
+		assert!(matches!(
+			evaluate("[1, 2][-3];"),
+			Err(InterpreterErrorKind::IndexOutOfBounds {
+				index: -3,
+				length: 2
+			})
+		));
+	}
+
+	#[test]
+	fn ranges() {
+		let source = "
+			let total = 0;
+			for i in 0..=10 by 2 {
+				total += i;
+			}
+			let result = [
+				total, len(10..0 by -3), [10, 11, 12, 13][1..3], 3 in 0..5, 3 in 0..5 by 2,
+				(0..0) == (5..5), (0..3) == (0..=2), (0..3) < (0..4), (1..2) > (0..9),
+			];
+		";
+		assert_eq!(
+			evaluate(source).unwrap(),
+			array([
+				Value::Integer(30),
+				Value::Integer(4),
+				integers([11, 12]),
+				Value::Bool(true),
+				Value::Bool(false),
+				Value::Bool(true),
+				Value::Bool(true),
+				Value::Bool(true),
+				Value::Bool(true),
+			])
+		);
+		assert!(matches!(
+			evaluate("0..5 by 0;"),
+			Err(InterpreterErrorKind::InvalidRangeStep)
+		));
+	}
+
+	#[test]
+	fn try_catch_finally() {
+		let source = r#"
+			let log = [];
+			let f = fn() {
+				try {
+					return 1;
+				} finally {
+					log = [...log, "finally"];
+				}
+			};
+			let error = null;
+			try {
+				let m = {};
+				m.missing;
+			} catch e {
+				error = e;
+			}
+			try {
+				throw {"code": 7};
+			} catch e {
+				log = [...log, e.code];
+			}
+			let result = [f(), error.kind, error.line, log];
+		"#;
+		assert_eq!(
+			evaluate(source).unwrap(),
+			array([
+				Value::Integer(1),
+				string("KeyNotInMap"),
+				Value::Integer(13),
+				array([Value::Integer(7), string("finally")]),
+			])
+		);
+		assert!(matches!(
+			evaluate("throw 1;"),
+			Err(InterpreterErrorKind::Thrown(Value::Integer(1)))
+		));
+	}
+
+	#[test]
+	fn match_patterns() {
+		let source = r#"
+			let describe = fn(value) {
+				return match value {
+					0 | 1 => "small",
+					[first, ...rest] => [first, len(rest)],
+					{"type": "point", x} => x,
+					n if n < 0 => "negative",
+					_ => "other",
+				};
+			};
+			let result = [describe(1), describe(-3), describe([4, 5, 6]), describe({"type": "point", "x": 2}), describe(9)];
+		"#;
+		assert_eq!(
+			evaluate(source).unwrap(),
+			array([
+				string("small"),
+				string("negative"),
+				integers([4, 2]),
+				Value::Integer(2),
+				string("other"),
+			])
+		);
+		assert!(matches!(
+			evaluate("match 3 { 1 => 1 };"),
+			Err(InterpreterErrorKind::NoMatch)
+		));
+	}
+
+	#[test]
+	fn match_arms_leave_no_scope_behind() {
+		let source = "
+			for i in 0..3 {
+				match i {
+					n if n == 1 => {
+						continue;
+					},
+					_ => null,
+				};
+			}
+			let result = i;
+		";
+		assert!(matches!(
+			evaluate(source),
+			Err(InterpreterErrorKind::UnknownIdentifier)
+		));
+	}
+
+	#[test]
+	fn destructuring() {
+		let source = r#"
+			let [a, b, ...rest] = [1, 2, 3, 4];
+			let {name, age: years} = {"name": "ada", "age": 36};
+			[a, b] = [b, a];
+			let m = {"name": "bob", "age": 7};
+			let n = null;
+			let age = null;
+			{"name": n, age} = m;
+			let sums = [];
+			for [k, v] in [[1, 2], [3, 4]] {
+				sums = [...sums, k + v];
+			}
+			let f = fn([x, y], {z}) { return x + y + z; };
+			let result = [a, b, rest, name, years, n, age, sums, f([1, 2], {"z": 3})];
+		"#;
+		assert_eq!(
+			evaluate(source).unwrap(),
+			array([
+				Value::Integer(2),
+				Value::Integer(1),
+				integers([3, 4]),
+				string("ada"),
+				Value::Integer(36),
+				string("bob"),
+				Value::Integer(7),
+				integers([3, 7]),
+				Value::Integer(6),
+			])
+		);
+		assert!(matches!(
+			evaluate("let [a, b] = [1];"),
+			Err(InterpreterErrorKind::WrongArrayLength {
+				expected: 2,
+				found: 1
+			})
+		));
+		assert!(matches!(
+			evaluate("let {a} = {};"),
+			Err(InterpreterErrorKind::KeyNotInMap)
+		));
+		assert!(matches!(
+			evaluate("let [a] = {};"),
+			Err(InterpreterErrorKind::ExpectedArray)
+		));
+	}
+
+	#[test]
+	fn spread_and_rest() {
+		let source = r#"
+			let xs = [2, 3];
+			let m = {...{"a": 1, "b": 2}, "b": 3};
+			let f = fn(first, ...rest) { return [first, rest]; };
+			let result = [[1, ...xs, ...0..2], m, f(...xs, 4), f(1)];
+		"#;
+		assert_eq!(
+			evaluate(source).unwrap(),
+			array([
+				integers([1, 2, 3, 0, 1]),
+				Value::Map(HashMap::from([
+					(HashableValue::String("a".to_owned()), Value::Integer(1)),
+					(HashableValue::String("b".to_owned()), Value::Integer(3)),
+				])),
+				array([Value::Integer(2), integers([3, 4])]),
+				array([Value::Integer(1), integers([])]),
+			])
+		);
+		assert!(matches!(
+			evaluate("fn(a) { return a; }(1, 2);"),
+			Err(InterpreterErrorKind::WrongArgumentCount)
+		));
+		assert!(matches!(
+			evaluate("[...1];"),
+			Err(InterpreterErrorKind::NotIterable)
+		));
+	}
+
+	#[test]
+	fn defaults_and_keyword_arguments() {
+		let source = "
+			let f = fn(a, b = a * 2, c = 0) { return [a, b, c]; };
+			let result = [f(1), f(1, c: 5), f(b: 3, a: 2)];
+		";
+		assert_eq!(
+			evaluate(source).unwrap(),
+			array([
+				integers([1, 2, 0]),
+				integers([1, 2, 5]),
+				integers([2, 3, 0])
+			])
+		);
+		let f = "let f = fn(a, b) { return a; };";
+		let call = |arguments: &str| evaluate(&format!("{f} f({arguments});"));
+		assert!(matches!(
+			call("c: 2"),
+			Err(InterpreterErrorKind::UnknownKeywordArgument(Identifier(name))) if name == "c"
+		));
+		assert!(matches!(
+			call("1, 2, a: 3"),
+			Err(InterpreterErrorKind::DuplicateArgument(Identifier(name))) if name == "a"
+		));
+		assert!(matches!(
+			call("b: 1, b: 2"),
+			Err(InterpreterErrorKind::DuplicateArgument(Identifier(name))) if name == "b"
+		));
+		assert!(matches!(
+			call("b: 2"),
+			Err(InterpreterErrorKind::WrongArgumentCount)
+		));
+		assert!(matches!(
+			evaluate("len([], a: 1);"),
+			Err(InterpreterErrorKind::UnknownKeywordArgument(_))
+		));
+	}
+
+	#[test]
+	fn methods_and_prototypes() {
+		let source = r#"
+			let counter = {
+				"count": 0,
+				"add": fn(n) {
+					self.count += n;
+					return self.count;
+				},
+			};
+			let child = {"proto": counter, "name": "child"};
+			let returned = counter.add(2);
+			counter.add(3);
+			const frozen = {"proto": counter, "count": 0};
+			let error = null;
+			try {
+				frozen.add(1);
+			} catch e {
+				error = e.kind;
+			}
+			let result = [returned, counter.count, child.count, child.name, error];
+		"#;
+		assert_eq!(
+			evaluate(source).unwrap(),
+			array([
+				Value::Integer(2),
+				Value::Integer(5),
+				Value::Integer(0),
+				string("child"),
+				string("AssignmentToConstant"),
+			])
+		);
+		assert!(matches!(
+			evaluate(r#"let m = {"proto": {}}; m.missing;"#),
+			Err(InterpreterErrorKind::KeyNotInMap)
+		));
+	}
+
+	/// A new directory for the files of a test, with the config allowed to read it
+	pub fn directory(name: &str) -> (PathBuf, Config) {
+		let directory = std::env::temp_dir().join(format!("mill-{name}-{}", std::process::id()));
+		_ = fs::remove_dir_all(&directory);
+		fs::create_dir_all(&directory).unwrap();
+		let config = Config {
+			capabilities: Capabilities {
+				fs_read: vec![directory.clone()],
+				..Capabilities::default()
+			},
+			..Config::default()
+		};
+		(directory, config)
+	}
+
+	#[test]
+	fn imports() {
+		let (directory, config) = directory("imports");
+		let counter = "
+			let count = 0;
+			export let bump = fn() {
+				count += 1;
+				return count;
+			};
+		";
+		fs::write(directory.join("counter.mill"), counter).unwrap();
+		fs::write(directory.join("a.mill"), r#"import "./b" as b;"#).unwrap();
+		fs::write(directory.join("b.mill"), r#"import "./a" as a;"#).unwrap();
+		let library = directory.join("library");
+		fs::create_dir(&library).unwrap();
+		fs::write(library.join("found.mill"), "export let value = 5;").unwrap();
+
+		// Both imports share the module, and its variables
+		let source = format!(
+			r#"
+				import "{0}/counter" as first;
+				from "{0}/./counter.mill" import bump;
+				first.bump();
+				let result = bump();
+			"#,
+			directory.display()
+		);
+		assert_eq!(
+			evaluate_with_config(&source, config.clone()).unwrap(),
+			Value::Integer(2)
+		);
+
+		let source = format!(r#"import "{}/a" as a;"#, directory.display());
+		let Err(InterpreterErrorKind::ImportCycle(chain)) =
+			evaluate_with_config(&source, config.clone())
+		else {
+			panic!("expected an import cycle");
+		};
+		let a = directory.join("a.mill").display().to_string();
+		let b = directory.join("b.mill").display().to_string();
+		assert_eq!(chain, [a.clone(), b, a]);
+
+		let source = format!(r#"import "{}/missing" as missing;"#, directory.display());
+		assert!(matches!(
+			evaluate_with_config(&source, config.clone()),
+			Err(InterpreterErrorKind::ModuleNotFound(_))
+		));
+
+		std::env::set_var("MILL_PATH", &library);
+		let mut config = config;
+		config.capabilities.fs_read.push(library);
+		assert_eq!(
+			evaluate_with_config(r#"from "found" import value; let result = value;"#, config)
+				.unwrap(),
+			Value::Integer(5)
+		);
+		fs::remove_dir_all(directory).unwrap();
+	}
+
+	#[test]
+	fn generators() {
+		let source = "
+			let count = fn(n) {
+				for i in 0..n {
+					try {
+						yield i;
+					} finally {
+						null;
+					}
+				}
+			};
+			let g = count(5);
+			let first = [];
+			for x in g {
+				first = [...first, x];
+				if x == 1 {
+					break;
+				}
+			}
+			let copy = g;
+			let next = copy.next();
+			let rest = [...g];
+			let result = [first, next, rest, g.next()];
+		";
+		assert_eq!(
+			evaluate(source).unwrap(),
+			array([
+				integers([0, 1]),
+				Value::Integer(2),
+				integers([3, 4]),
+				Value::Null
+			])
+		);
+		let source = "
+			let g = null;
+			let body = fn() {
+				yield g.next();
+			};
+			g = body();
+			g.next();
+		";
+		assert!(matches!(
+			evaluate(source),
+			Err(InterpreterErrorKind::GeneratorRunning)
+		));
+	}
+
+	#[test]
+	fn labeled_breaks_with_values() {
+		let source = "
+			let found = 'search: loop {
+				for i in 0..10 {
+					loop {
+						if i * i > 20 {
+							break 'search i;
+						}
+						break;
+					}
+				}
+			};
+			let skipped = 0;
+			'outer: for i in 0..3 {
+				for j in 0..3 {
+					if j == 1 {
+						continue 'outer;
+					}
+					skipped += 1;
+				}
+			}
+			let result = [found, skipped, loop { break; }];
+		";
+		assert_eq!(
+			evaluate(source).unwrap(),
+			array([Value::Integer(5), Value::Integer(3), Value::Null])
+		);
+	}
+
+	#[test]
+	fn expression_blocks() {
+		let source = r#"
+			let sign = fn(n) {
+				return if n < 0 { "negative" } else if n == 0 { "zero" } else { "positive" };
+			};
+			let block = {
+				let x = 2;
+				x * 3
+			};
+			let kind = match block {
+				6 => "six",
+				_ => "other",
+			};
+			match kind {
+				"six" => null,
+				_ => null,
+			}
+			let empty = if false { 1 };
+			let result = [sign(-1), sign(0), sign(3), block, kind, empty];
+		"#;
+		assert_eq!(
+			evaluate(source).unwrap(),
+			array([
+				string("negative"),
+				string("zero"),
+				string("positive"),
+				Value::Integer(6),
+				string("six"),
+				Value::Null,
+			])
+		);
+	}
+
+	#[test]
+	fn optional_chaining_and_coalescing() {
+		let source = r#"
+			let calls = 0;
+			let touch = fn() {
+				calls += 1;
+				return true;
+			};
+			let user = {"address": {"city": "Oslo"}, "tags": []};
+			let none = null;
+			let result = [
+				user?.address?.city, none?.address.city, user?.missing, user.tags?.[0],
+				none ?? "default", 0 ?? "default", false && touch(), true || touch(), calls,
+			];
+		"#;
+		assert_eq!(
+			evaluate(source).unwrap(),
+			array([
+				string("Oslo"),
+				Value::Null,
+				Value::Null,
+				Value::Null,
+				string("default"),
+				Value::Integer(0),
+				Value::Bool(false),
+				Value::Bool(true),
+				Value::Integer(0),
+			])
+		);
+		assert!(matches!(
+			evaluate(r#"let user = {"address": {}}; user?.address.city;"#),
+			Err(InterpreterErrorKind::KeyNotInMap)
+		));
+	}
+
+	#[test]
+	fn constants() {
+		let source = r#"
+			const limits = {"max": 3, "list": [1]};
+			let copy = limits;
+			copy.max = 4;
+			let result = [limits.max, copy.max];
+		"#;
+		assert_eq!(evaluate(source).unwrap(), integers([3, 4]));
+		for change in [
+			"limits = 1;",
+			"limits.max = 4;",
+			"limits.list[0] += 1;",
+			"limits.x = 1;",
+		] {
+			let source = format!(r#"const limits = {{"max": 3, "list": [1]}}; {change}"#);
+			assert!(
+				matches!(
+					evaluate(&source),
+					Err(InterpreterErrorKind::AssignmentToConstant(Identifier(name))) if name == "limits"
+				),
+				"{change}"
+			);
+		}
+		// Shadowing in an inner scope is still allowed
+		assert_eq!(
+			evaluate("const x = 1; let result = { let x = 2; x };").unwrap(),
+			Value::Integer(2)
+		);
+	}
+
+	#[test]
+	fn deep_recursion() {
+		let source = "
+			let depth = fn(n) {
+				if n == 0 {
+					return 0;
+				}
+				return 1 + depth(n - 1);
+			};
+			let result = depth(1000);
+		";
+		assert_eq!(evaluate(source).unwrap(), Value::Integer(1000));
+		let config = Config {
+			max_call_depth: 100,
+			..Config::default()
+		};
+		assert!(matches!(
+			evaluate_with_config(source, config),
+			Err(InterpreterErrorKind::StackOverflow)
+		));
+	}
+
+	#[test]
+	fn limits() {
+		let fuel = Config {
+			fuel: Some(1000),
+			..Config::default()
+		};
+		let deadline = Config {
+			deadline: Some(Instant::now()),
+			..Config::default()
+		};
+		let cancellation = CancellationHandle::new();
+		cancellation.cancel();
+		let cancelled = Config {
+			cancellation: Some(cancellation),
+			..Config::default()
+		};
+		// Scripts can't catch them, and `finally` doesn't run
+		let source = "
+			try {
+				loop {}
+			} catch e {
+				let result = e;
+			} finally {
+				throw 1;
+			}
+		";
+		assert!(matches!(
+			evaluate_with_config(source, fuel),
+			Err(InterpreterErrorKind::OutOfFuel)
+		));
+		assert!(matches!(
+			evaluate_with_config(source, deadline),
+			Err(InterpreterErrorKind::DeadlineExceeded)
+		));
+		assert!(matches!(
+			evaluate_with_config(source, cancelled),
+			Err(InterpreterErrorKind::Cancelled)
+		));
+
+		let cancellation = CancellationHandle::new();
+		let config = Config {
+			cancellation: Some(cancellation.clone()),
+			..Config::default()
+		};
+		let thread = std::thread::spawn(move || {
+			std::thread::sleep(std::time::Duration::from_millis(10));
+			cancellation.cancel();
+		});
+		assert!(matches!(
+			evaluate_with_config(source, config),
+			Err(InterpreterErrorKind::Cancelled)
+		));
+		thread.join().unwrap();
+	}
+
+	#[test]
+	fn allocation_limit() {
+		let config = Config {
+			allocation_limit: Some(1 << 16),
+			..Config::default()
+		};
+		let small = "let m = {}; for i in 0..10 { m[i] = i; } let result = len(m);";
+		assert_eq!(
+			evaluate_with_config(small, config.clone()).unwrap(),
+			Value::Integer(10)
+		);
+		for source in [
+			// New map entries
+			"let m = {}; for i in 0..100000 { m[i] = i; }",
+			// Copies of existing values
+			r#"let s = "x" * 1000; for i in 0..100 { let copy = s; }"#,
+			r#"let s = "x" * 1000; let a = [s]; for i in 0..100 { a[0]; }"#,
+			r#"let s = "x" * 1000; for i in 0..100 { [...[s]]; }"#,
+			// Everything allocated is counted, even once it's no longer used
+			r#"for i in 0..100000 { "temporary" + "string"; }"#,
+			"try { let m = {}; for i in 0..100000 { m[i] = i; } } catch e {}",
+		] {
+			assert!(
+				matches!(
+					evaluate_with_config(source, config.clone()),
+					Err(InterpreterErrorKind::AllocationLimitExceeded)
+				),
+				"{source}"
+			);
+		}
+	}
+
+	#[test]
+	fn imports_need_read_permission() {
+		let (directory, config) = directory("import-permission");
+		let hidden = directory.join("hidden");
+		fs::create_dir(&hidden).unwrap();
+		fs::write(hidden.join("module.mill"), "").unwrap();
+		let config = Config {
+			capabilities: Capabilities {
+				fs_read: vec![directory.join("readable")],
+				..config.capabilities
+			},
+			..config
+		};
+		// Whether the file exists isn't revealed
+		for name in ["module", "missing"] {
+			let source = format!(r#"import "{}/{name}" as m;"#, hidden.display());
+			assert!(
+				matches!(
+					evaluate_with_config(&source, config.clone()),
+					Err(InterpreterErrorKind::PermissionDenied(_))
+				),
+				"{name}"
+			);
+		}
+		fs::remove_dir_all(directory).unwrap();
+	}
+
+	#[test]
+	fn tail_calls_see_the_caller_variables() {
+		let source = "
+			let outer = fn(n) {
+				let helper = fn(k) {
+					if k == 0 {
+						return 0;
+					}
+					return helper(k - 1);
+				};
+				return helper(n);
+			};
+			let read = fn() { return y; };
+			let inner = fn() {
+				if true {
+					let y = 7;
+					return read();
+				}
+			};
+			if outer(3) != 0 || inner() != 7 {
+				throw 1;
+			}
+		";
+		assert!(interpret(parse(source).unwrap()).is_ok());
+	}
+}
@@ -1,6 +1,14 @@
+mod builtins;
+mod config;
 mod error;
+mod generator;
 mod interpret;
+mod module;
+mod pattern;
 mod state;
+mod stdlib;
 mod value;
 
-pub use interpret::interpret;
+pub use config::{CancellationHandle, Capabilities, Config};
+pub use error::{InterpreterError, InterpreterErrorKind};
+pub use interpret::{interpret, interpret_with_config};
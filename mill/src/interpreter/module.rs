@@ -0,0 +1,78 @@
+use std::{
+	env,
+	path::{Component, Path, PathBuf},
+};
+
+use crate::interpreter::{state::Scope, value::Value};
+
+/// Index of a module in `State::modules`
+pub type ModuleId = usize;
+
+/// The script passed to `interpret`
+pub const MAIN_MODULE: ModuleId = 0;
+
+/// Directories searched for imports that aren't found next to the importing file
+const SEARCH_PATH_VARIABLE: &str = "MILL_PATH";
+
+#[derive(Debug)]
+pub struct Module {
+	/// Path the module was found at, shown in errors
+	pub name: Option<String>,
+	/// Canonical path, used to evaluate each module once.
+	/// `None` if the module failed to evaluate, so importing it again retries.
+	pub path: Option<PathBuf>,
+	/// The module's global scope, taken out while one of its functions is running
+	pub globals: Option<Scope>,
+	/// Map of exported names, `None` while the module is being evaluated
+	pub exports: Option<Value>,
+}
+
+impl Module {
+	pub fn new(name: Option<String>, path: Option<PathBuf>) -> Self {
+		Self {
+			name: name.map(|name| normalize(Path::new(&name)).display().to_string()),
+			path,
+			globals: None,
+			exports: None,
+		}
+	}
+}
+
+/// The files an import could refer to, in the order they're looked for.
+/// Paths starting with `.` or `..` are only looked up next to the importing file.
+pub fn candidates(path: &str, importer: Option<&Path>) -> Vec<PathBuf> {
+	let mut path = PathBuf::from(path);
+	if path.extension().is_none() {
+		path.set_extension("mill");
+	}
+	let base = importer
+		.and_then(Path::parent)
+		.unwrap_or_else(|| Path::new(""));
+
+	let mut candidates = vec![base.join(&path)];
+	let relative_only = path.starts_with(".") || path.starts_with("..");
+	if !relative_only && !path.is_absolute() {
+		if let Some(search_path) = env::var_os(SEARCH_PATH_VARIABLE) {
+			candidates
+				.extend(env::split_paths(&search_path).map(|directory| directory.join(&path)));
+		}
+	}
+
+	candidates
+		.into_iter()
+		.map(|candidate| normalize(&candidate))
+		.collect()
+}
+
+/// Leaves out the `.` components of a path, so the same file is shown the same way in errors
+fn normalize(path: &Path) -> PathBuf {
+	let normalized = path
+		.components()
+		.filter(|component| *component != Component::CurDir)
+		.collect::<PathBuf>();
+	if normalized.as_os_str().is_empty() {
+		PathBuf::from(".")
+	} else {
+		normalized
+	}
+}
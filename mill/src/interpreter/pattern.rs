@@ -0,0 +1,90 @@
+use crate::{
+	ast::{Expression, Identifier, Pattern},
+	interpreter::{error::InterpreterErrorKind, value::Value},
+};
+
+/// Checks whether the value matches the pattern, collecting the bindings it makes.
+/// The bindings are left partially filled if it doesn't match.
+pub fn match_pattern(
+	pattern: &Pattern,
+	value: &Value,
+	bindings: &mut Vec<(Identifier, Value)>,
+) -> bool {
+	destructure(pattern, value, bindings).is_ok()
+}
+
+/// Like `match_pattern`, but reports why the value doesn't match
+pub fn destructure(
+	pattern: &Pattern,
+	value: &Value,
+	bindings: &mut Vec<(Identifier, Value)>,
+) -> Result<(), InterpreterErrorKind> {
+	match pattern {
+		Pattern::Wildcard => {},
+		Pattern::Binding(name) => bindings.push((name.clone(), value.clone())),
+		Pattern::Literal(literal) => {
+			if literal_value(literal) != *value {
+				return Err(InterpreterErrorKind::PatternMismatch);
+			}
+		},
+		Pattern::Array(elements, rest) => {
+			let Value::Array(array) = value else {
+				return Err(InterpreterErrorKind::ExpectedArray);
+			};
+			let length_matches = match rest {
+				Some(_) => array.len() >= elements.len(),
+				None => array.len() == elements.len(),
+			};
+			if !length_matches {
+				return Err(InterpreterErrorKind::WrongArrayLength {
+					expected: elements.len(),
+					found: array.len(),
+				});
+			}
+			for (pattern, value) in elements.iter().zip(array) {
+				destructure(pattern, value, bindings)?;
+			}
+			if let Some(rest) = rest {
+				let remaining = Value::Array(array[elements.len()..].to_vec());
+				destructure(rest, &remaining, bindings)?;
+			}
+		},
+		Pattern::Map(entries) => {
+			let Value::Map(map) = value else {
+				return Err(InterpreterErrorKind::ExpectedMap);
+			};
+			for (key, pattern) in entries {
+				let Some(key) = literal_value(key).try_as_hashable() else {
+					return Err(InterpreterErrorKind::MapKeyNotHashable);
+				};
+				let Some(value) = map.get(&key) else {
+					return Err(InterpreterErrorKind::KeyNotInMap);
+				};
+				destructure(pattern, value, bindings)?;
+			}
+		},
+		Pattern::Or(alternatives) => {
+			let len = bindings.len();
+			let matched = alternatives.iter().any(|alternative| {
+				bindings.truncate(len);
+				match_pattern(alternative, value, bindings)
+			});
+			if !matched {
+				return Err(InterpreterErrorKind::PatternMismatch);
+			}
+		},
+	}
+	Ok(())
+}
+
+fn literal_value(literal: &Expression) -> Value {
+	match literal {
+		Expression::True => Value::Bool(true),
+		Expression::False => Value::Bool(false),
+		Expression::Null => Value::Null,
+		Expression::Integer(i) => Value::Integer(*i),
+		Expression::Float(f) => Value::Float(*f),
+		Expression::String(s) => Value::String(s.clone()),
+		_ => unreachable!("literal patterns are checked by the parser"),
+	}
+}
@@ -1,16 +1,89 @@
-use std::collections::HashMap;
+use std::{
+	collections::{HashMap, HashSet},
+	time::Instant,
+};
 
 use crate::{
 	ast::{Expression, Identifier},
-	interpreter::{error::InterpreterError, value::Value},
+	interpreter::{
+		config::{CancellationHandle, Config},
+		error::InterpreterErrorKind,
+		module::{Module, ModuleId, MAIN_MODULE},
+		value::Value,
+	},
+	parser::LineNumber,
 };
 
 #[derive(Debug)]
 pub struct State {
 	pub stack: Vec<Scope>,
+	/// Line of the statement being executed, used to locate errors
+	pub line_number: LineNumber,
+	/// Module of the statement being executed
+	pub module: ModuleId,
+	/// Every module imported so far, starting with the main module
+	pub modules: Vec<Module>,
+	pub config: Config,
+	/// Calls of script functions currently running
+	pub call_depth: usize,
+	/// Steps counted against `Config::fuel`
+	pub steps: u64,
+	/// Bytes allocated so far, counted against `Config::allocation_limit`
+	pub allocated: usize,
+	/// State of the generator of `std/random`, zero until it's first used
+	pub random: u64,
 }
 impl State {
-	pub fn new() -> Self { Self { stack: Vec::new() } }
+	pub fn new(config: Config) -> Self {
+		Self {
+			stack: Vec::new(),
+			line_number: 1,
+			module: MAIN_MODULE,
+			modules: Vec::new(),
+			config,
+			call_depth: 0,
+			steps: 0,
+			allocated: 0,
+			random: 0,
+		}
+	}
+
+	/// Counts a step of the script against its fuel, and checks its deadline and cancellation
+	pub fn step(&mut self) -> Result<(), InterpreterErrorKind> {
+		self.steps += 1;
+		if self.config.fuel.is_some_and(|fuel| self.steps > fuel) {
+			return Err(InterpreterErrorKind::OutOfFuel);
+		}
+		if self
+			.config
+			.deadline
+			.is_some_and(|deadline| Instant::now() >= deadline)
+		{
+			return Err(InterpreterErrorKind::DeadlineExceeded);
+		}
+		if self
+			.config
+			.cancellation
+			.as_ref()
+			.is_some_and(CancellationHandle::is_cancelled)
+		{
+			return Err(InterpreterErrorKind::Cancelled);
+		}
+		Ok(())
+	}
+
+	/// Counts bytes of a value about to be built or copied against the allocation limit
+	pub fn allocate(&mut self, bytes: usize) -> Result<(), InterpreterErrorKind> {
+		self.allocated = self.allocated.saturating_add(bytes);
+		if self
+			.config
+			.allocation_limit
+			.is_some_and(|limit| self.allocated > limit)
+		{
+			return Err(InterpreterErrorKind::AllocationLimitExceeded);
+		}
+		Ok(())
+	}
 
 	pub fn push(&mut self) { self.stack.push(Scope::new()); }
 
@@ -22,23 +95,52 @@ impl State {
 	}
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Scope {
 	pub variables: HashMap<Identifier, Value>,
+	/// Variables that can't be assigned to, or changed through their members or methods
+	pub constants: HashSet<Identifier>,
 }
 
 impl Scope {
 	pub fn new() -> Self {
 		Self {
 			variables: HashMap::new(),
+			constants: HashSet::new(),
+		}
+	}
+
+	/// Adds the variables of a scope that was above this one, shadowing those of the same name
+	pub fn merge(&mut self, above: Self) {
+		for (name, value) in above.variables {
+			if above.constants.contains(&name) {
+				self.constants.insert(name.clone());
+			} else {
+				self.constants.remove(&name);
+			}
+			self.variables.insert(name, value);
 		}
 	}
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ControlFlow {
 	Normal,
-	Break,
-	Continue,
+	/// Leaves the loop with the label, or the innermost loop, giving it a value
+	Break(Option<Identifier>, Value),
+	Continue(Option<Identifier>),
 	Return(Value),
+	/// `return f(x)`, where `f` is called once the returning function has finished.
+	/// The scopes of the blocks it was made in are left on the stack, so `f` can still see them.
+	TailCall {
+		function: Value,
+		arguments: Vec<Value>,
+		keyword_arguments: Vec<(Identifier, Value)>,
+	},
+}
+impl ControlFlow {
+	/// Whether a `break` or `continue` with the label leaves the loop with `loop_label`
+	pub fn targets(label: Option<&Identifier>, loop_label: Option<&Identifier>) -> bool {
+		label.is_none() || label == loop_label
+	}
 }
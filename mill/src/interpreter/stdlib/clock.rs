@@ -0,0 +1,23 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::interpreter::{
+	error::InterpreterErrorKind,
+	state::State,
+	stdlib::{arguments, require},
+	value::{NativeFunction, Value},
+};
+
+pub const FUNCTIONS: &[NativeFunction] = &[NativeFunction {
+	name: "clock.now",
+	function: now,
+}];
+
+/// Seconds since the unix epoch, as a float
+fn now(state: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	require(state.config.capabilities.clock, "read the clock")?;
+	let [] = self::arguments(arguments)?;
+	let elapsed = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map_err(|e| InterpreterErrorKind::Io(e.to_string()))?;
+	Ok(Value::Float(elapsed.as_secs_f64()))
+}
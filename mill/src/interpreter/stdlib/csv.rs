@@ -0,0 +1,323 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::interpreter::{
+	error::InterpreterErrorKind,
+	state::State,
+	stdlib::{
+		cursor::{encode_error, Cursor},
+		option, optional_arguments, options, string,
+	},
+	value::{HashableValue, NativeFunction, Value},
+};
+
+pub const FUNCTIONS: &[NativeFunction] = &[
+	NativeFunction {
+		name: "csv.parse",
+		function: parse,
+	},
+	NativeFunction {
+		name: "csv.stringify",
+		function: stringify,
+	},
+];
+
+/// The `"delimiter"` option, a single character that defaults to a comma
+fn delimiter(options: &mut HashMap<HashableValue, Value>) -> Result<char, InterpreterErrorKind> {
+	let Some(delimiter) = option(options, "delimiter") else {
+		return Ok(',');
+	};
+	let delimiter = string(delimiter)?;
+	let mut chars = delimiter.chars();
+	match (chars.next(), chars.next()) {
+		(Some(delimiter), None) if !matches!(delimiter, '"' | '\r' | '\n') => Ok(delimiter),
+		_ => Err(InterpreterErrorKind::UnsupportedOperation),
+	}
+}
+
+/// Decodes CSV text, where fields can be quoted with `"` and quotes in them are doubled.
+/// Takes an optional map of options, with the `"delimiter"` and whether the first row is a
+/// `"header"`. With a header, which is the default, each row becomes a map from the names in
+/// the header to the fields, otherwise each row becomes an array. Fields are always strings.
+fn parse(state: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	let ([text], options) = optional_arguments(arguments)?;
+	let text = string(text)?;
+	let mut options = self::options(options)?;
+	let delimiter = delimiter(&mut options)?;
+	let header = match option(&mut options, "header") {
+		None => true,
+		Some(Value::Bool(header)) => header,
+		Some(_) => return Err(InterpreterErrorKind::ExpectedBool),
+	};
+
+	let mut cursor = Cursor::new(&text, "CSV");
+	let mut names = None::<Vec<String>>;
+	let mut rows = Vec::new();
+	while cursor.peek().is_some() {
+		let fields = parse_row(&mut cursor, delimiter)?;
+		// Blank lines are skipped
+		let blank = matches!(fields.as_slice(), [field] if field.is_empty());
+		if let Some(names) = names.as_ref().filter(|_| !blank) {
+			if fields.len() != names.len() {
+				return Err(cursor.error(format!(
+					"row has {} fields, but the header has {}",
+					fields.len(),
+					names.len()
+				)));
+			}
+		} else if header && !blank {
+			// Each name becomes a key of the rows, so it can't be repeated
+			let repeated = (1..fields.len()).find(|&i| fields[..i].contains(&fields[i]));
+			if let Some(i) = repeated {
+				return Err(cursor.error(format!(
+					"header has the name '{}' more than once",
+					fields[i]
+				)));
+			}
+		}
+		cursor.eat('\r');
+		cursor.eat('\n');
+
+		if blank {
+			continue;
+		}
+		if !header {
+			rows.push(Value::Array(
+				fields.into_iter().map(Value::String).collect(),
+			));
+			continue;
+		}
+		let Some(names) = &names else {
+			names = Some(fields);
+			continue;
+		};
+		rows.push(Value::Map(
+			names
+				.iter()
+				.map(|name| HashableValue::String(name.clone()))
+				.zip(fields.into_iter().map(Value::String))
+				.collect(),
+		));
+	}
+
+	let value = Value::Array(rows);
+	state.allocate(value.heap_size())?;
+	Ok(value)
+}
+
+/// Parses the fields of a row, leaving the cursor at the end of its line
+fn parse_row(cursor: &mut Cursor, delimiter: char) -> Result<Vec<String>, InterpreterErrorKind> {
+	let mut fields = Vec::new();
+	loop {
+		let mut field = String::new();
+		if cursor.eat('"') {
+			loop {
+				match cursor.next() {
+					Some('"') if !cursor.eat('"') => break,
+					Some(c) => field.push(c),
+					None => return Err(cursor.error("unterminated quoted field")),
+				}
+			}
+		} else {
+			while let Some(c) = cursor
+				.peek()
+				.filter(|&c| c != delimiter && !matches!(c, '\r' | '\n'))
+			{
+				field.push(c);
+				cursor.next();
+			}
+		}
+		fields.push(field);
+
+		match cursor.peek() {
+			Some(c) if c == delimiter => {
+				cursor.next();
+			},
+			Some('\r' | '\n') | None => break,
+			Some(c) => {
+				return Err(cursor.error(format!(
+					"expected a delimiter after a quoted field, found '{c}'"
+				)))
+			},
+		}
+	}
+	Ok(fields)
+}
+
+/// Encodes an array of rows as CSV text, quoting the fields that need it.
+/// The rows are either all arrays, or all maps whose keys become the header.
+/// Takes an optional map of options, with the `"delimiter"` and the `"columns"` of rows
+/// that are maps, which otherwise are every key of the rows in sorted order.
+fn stringify(state: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	let ([rows], options) = optional_arguments(arguments)?;
+	let Value::Array(rows) = rows else {
+		return Err(InterpreterErrorKind::ExpectedArray);
+	};
+	let mut options = self::options(options)?;
+	let delimiter = delimiter(&mut options)?;
+	let columns = match option(&mut options, "columns") {
+		None => None,
+		Some(Value::Array(columns)) => Some(
+			columns
+				.into_iter()
+				.map(string)
+				.collect::<Result<Vec<_>, _>>()?,
+		),
+		Some(_) => return Err(InterpreterErrorKind::ExpectedArray),
+	};
+
+	let mut output = String::new();
+	if let Some(Value::Map(_)) = rows.first() {
+		let columns = columns.map_or_else(|| all_columns(&rows), Ok)?;
+		let header = columns
+			.iter()
+			.cloned()
+			.map(Value::String)
+			.collect::<Vec<_>>();
+		write_row(&mut output, &header, delimiter)?;
+		for row in rows {
+			let Value::Map(mut row) = row else {
+				return Err(InterpreterErrorKind::ExpectedMap);
+			};
+			let fields = columns
+				.iter()
+				.map(|column| {
+					row.remove(&HashableValue::String(column.clone()))
+						.unwrap_or(Value::Null)
+				})
+				.collect::<Vec<_>>();
+			write_row(&mut output, &fields, delimiter)?;
+		}
+	} else {
+		for row in rows {
+			let Value::Array(fields) = row else {
+				return Err(InterpreterErrorKind::ExpectedArray);
+			};
+			write_row(&mut output, &fields, delimiter)?;
+		}
+	}
+
+	state.allocate(output.len())?;
+	Ok(Value::String(output))
+}
+
+/// Every key of rows that are maps, sorted
+fn all_columns(rows: &[Value]) -> Result<Vec<String>, InterpreterErrorKind> {
+	let mut columns = BTreeSet::new();
+	for row in rows {
+		let Value::Map(row) = row else {
+			return Err(InterpreterErrorKind::ExpectedMap);
+		};
+		for key in row.keys() {
+			let HashableValue::String(key) = key else {
+				return Err(encode_error(
+					"CSV",
+					"map keys must be strings to be columns",
+				));
+			};
+			columns.insert(key.clone());
+		}
+	}
+	Ok(columns.into_iter().collect())
+}
+
+/// Writes a line of fields, where `null` is an empty field
+fn write_row(
+	output: &mut String,
+	fields: &[Value],
+	delimiter: char,
+) -> Result<(), InterpreterErrorKind> {
+	for (i, field) in fields.iter().enumerate() {
+		if i > 0 {
+			output.push(delimiter);
+		}
+		let field = match field {
+			Value::Null => String::new(),
+			Value::Bool(bool) => bool.to_string(),
+			Value::Integer(integer) => integer.to_string(),
+			Value::Float(float) => float.to_string(),
+			Value::String(string) => string.clone(),
+			value => {
+				return Err(encode_error(
+					"CSV",
+					format!("only strings, numbers, bools and null can be fields, found {value:?}"),
+				))
+			},
+		};
+		if field.contains([delimiter, '"', '\r', '\n']) {
+			output.push('"');
+			output.push_str(&field.replace('"', "\"\""));
+			output.push('"');
+		} else {
+			output.push_str(&field);
+		}
+	}
+	output.push('\n');
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+
+	use crate::interpreter::{
+		error::InterpreterErrorKind,
+		interpret::tests::{array, evaluate, string},
+		value::{HashableValue, Value},
+	};
+
+	fn csv(call: &str) -> Result<Value, InterpreterErrorKind> {
+		evaluate(&format!(r#"import "std/csv" as csv; let result = {call};"#))
+	}
+
+	fn row(fields: &[(&str, &str)]) -> Value {
+		Value::Map(
+			fields
+				.iter()
+				.map(|&(name, field)| (HashableValue::String(name.to_owned()), string(field)))
+				.collect::<HashMap<_, _>>(),
+		)
+	}
+
+	#[test]
+	fn round_trip() {
+		assert_eq!(
+			csv("csv.parse(\"name;note\nada;x\n\nbob;\n\", {\"delimiter\": \";\"})").unwrap(),
+			array([
+				row(&[("name", "ada"), ("note", "x")]),
+				row(&[("name", "bob"), ("note", "")])
+			])
+		);
+		assert_eq!(
+			csv(r#"csv.stringify([{"b": "x,y", "a": 1}, {"a": null}])"#).unwrap(),
+			string("a,b\n1,\"x,y\"\n,\n")
+		);
+		assert_eq!(
+			csv(r#"csv.parse(csv.stringify([{"b": "x,y", "a": "1"}]))"#).unwrap(),
+			array([row(&[("a", "1"), ("b", "x,y")])])
+		);
+		assert_eq!(
+			csv("csv.parse(\"a,b\n1,2\", {\"header\": false})").unwrap(),
+			array([
+				array([string("a"), string("b")]),
+				array([string("1"), string("2")])
+			])
+		);
+	}
+
+	#[test]
+	fn errors() {
+		let Err(InterpreterErrorKind::Decode { line, .. }) = csv("csv.parse(\"a,b\n1,2,3\")")
+		else {
+			panic!("expected a decode error");
+		};
+		assert_eq!(line, 2);
+		assert!(matches!(
+			csv("csv.parse(\"a,a\n1,2\")"),
+			Err(InterpreterErrorKind::Decode { line: 1, .. })
+		));
+		assert!(matches!(
+			csv("csv.stringify([[[1]]])"),
+			Err(InterpreterErrorKind::Encode { .. })
+		));
+	}
+}
@@ -0,0 +1,99 @@
+use std::{fmt::Write, iter::Peekable, str::Chars};
+
+use crate::interpreter::error::InterpreterErrorKind;
+
+/// Arrays and maps nested deeper than this are rejected by the decoders and encoders,
+/// so they can't overflow the stack
+pub const MAX_DEPTH: usize = 256;
+
+/// Reads text a character at a time, tracking the position for errors of the decoders
+pub struct Cursor<'a> {
+	chars: Peekable<Chars<'a>>,
+	/// Name of the format being decoded
+	format: &'static str,
+	/// Position of the next character
+	line: usize,
+	column: usize,
+}
+
+impl<'a> Cursor<'a> {
+	pub fn new(text: &'a str, format: &'static str) -> Self {
+		Self {
+			chars: text.chars().peekable(),
+			format,
+			line: 1,
+			column: 1,
+		}
+	}
+
+	pub fn error(&self, message: impl Into<String>) -> InterpreterErrorKind {
+		InterpreterErrorKind::Decode {
+			format: self.format,
+			line: self.line,
+			column: self.column,
+			message: message.into(),
+		}
+	}
+
+	pub fn peek(&mut self) -> Option<char> { self.chars.peek().copied() }
+
+	/// The character after the next one
+	pub fn peek_second(&self) -> Option<char> { self.chars.clone().nth(1) }
+
+	pub fn next(&mut self) -> Option<char> {
+		let c = self.chars.next()?;
+		if c == '\n' {
+			self.line += 1;
+			self.column = 1;
+		} else {
+			self.column += 1;
+		}
+		Some(c)
+	}
+
+	/// Consumes the next character if it's the given one
+	pub fn eat(&mut self, expected: char) -> bool {
+		let found = self.peek() == Some(expected);
+		if found {
+			self.next();
+		}
+		found
+	}
+
+	pub fn expect(&mut self, expected: char) -> Result<(), InterpreterErrorKind> {
+		match self.peek() {
+			Some(c) if c == expected => {
+				self.next();
+				Ok(())
+			},
+			Some(c) => Err(self.error(format!("expected '{expected}', found '{c}'"))),
+			None => Err(self.error(format!("expected '{expected}', found the end of the text"))),
+		}
+	}
+}
+
+pub fn encode_error(format: &'static str, message: impl Into<String>) -> InterpreterErrorKind {
+	InterpreterErrorKind::Encode {
+		format,
+		message: message.into(),
+	}
+}
+
+/// Writes a string in double quotes, with the escapes JSON and TOML have in common
+pub fn write_string(output: &mut String, string: &str) {
+	output.push('"');
+	for c in string.chars() {
+		match c {
+			'"' => output.push_str("\\\""),
+			'\\' => output.push_str("\\\\"),
+			'\n' => output.push_str("\\n"),
+			'\r' => output.push_str("\\r"),
+			'\t' => output.push_str("\\t"),
+			'\u{8}' => output.push_str("\\b"),
+			'\u{c}' => output.push_str("\\f"),
+			c if c.is_control() => write!(output, "\\u{:04x}", u32::from(c)).unwrap(),
+			c => output.push(c),
+		}
+	}
+	output.push('"');
+}
@@ -0,0 +1,42 @@
+use std::env;
+
+use crate::interpreter::{
+	error::InterpreterErrorKind,
+	state::State,
+	stdlib::{arguments, require, string},
+	value::{HashableValue, NativeFunction, Value},
+};
+
+pub const FUNCTIONS: &[NativeFunction] = &[
+	NativeFunction {
+		name: "env.get",
+		function: get,
+	},
+	NativeFunction {
+		name: "env.vars",
+		function: vars,
+	},
+];
+
+/// The value of an environment variable, or `null` if it isn't set
+fn get(state: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	require(state.config.capabilities.env, "read environment variables")?;
+	let [name] = self::arguments(arguments)?;
+	Ok(env::var(string(name)?).map_or(Value::Null, Value::String))
+}
+
+/// Map of every environment variable, leaving out those that aren't valid unicode
+fn vars(state: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	require(state.config.capabilities.env, "read environment variables")?;
+	let [] = self::arguments(arguments)?;
+	Ok(Value::Map(
+		env::vars_os()
+			.filter_map(|(name, value)| {
+				Some((
+					HashableValue::String(name.into_string().ok()?),
+					Value::String(value.into_string().ok()?),
+				))
+			})
+			.collect(),
+	))
+}
@@ -0,0 +1,549 @@
+use std::{
+	fs::{self, OpenOptions},
+	io::Write,
+	path::{Component, Path, PathBuf},
+};
+
+use crate::interpreter::{
+	error::InterpreterErrorKind,
+	state::State,
+	stdlib::{arguments, require, string},
+	value::{NativeFunction, Value},
+};
+
+pub const FUNCTIONS: &[NativeFunction] = &[
+	NativeFunction {
+		name: "fs.read_text",
+		function: read_text,
+	},
+	NativeFunction {
+		name: "fs.read_lines",
+		function: read_lines,
+	},
+	NativeFunction {
+		name: "fs.write_text",
+		function: write_text,
+	},
+	NativeFunction {
+		name: "fs.append",
+		function: append,
+	},
+	NativeFunction {
+		name: "fs.exists",
+		function: exists,
+	},
+	NativeFunction {
+		name: "fs.list_dir",
+		function: list_dir,
+	},
+	NativeFunction {
+		name: "fs.mkdir",
+		function: mkdir,
+	},
+	NativeFunction {
+		name: "fs.remove",
+		function: remove,
+	},
+	NativeFunction {
+		name: "fs.glob",
+		function: glob,
+	},
+	NativeFunction {
+		name: "fs.matches",
+		function: matches,
+	},
+	NativeFunction {
+		name: "fs.join",
+		function: join,
+	},
+	NativeFunction {
+		name: "fs.split",
+		function: split,
+	},
+	NativeFunction {
+		name: "fs.parent",
+		function: parent,
+	},
+	NativeFunction {
+		name: "fs.file_name",
+		function: file_name,
+	},
+	NativeFunction {
+		name: "fs.stem",
+		function: stem,
+	},
+	NativeFunction {
+		name: "fs.extension",
+		function: extension,
+	},
+];
+
+/// Takes the path argument of a function that reads, checking the script may read it
+fn readable(state: &State, path: Value) -> Result<PathBuf, InterpreterErrorKind> {
+	let path = PathBuf::from(string(path)?);
+	check_readable(state, &path)?;
+	Ok(path)
+}
+
+fn check_readable(state: &State, path: &Path) -> Result<(), InterpreterErrorKind> {
+	require(
+		state.config.capabilities.can_read(path),
+		&format!("read '{}'", path.display()),
+	)
+}
+
+/// Takes the path argument of a function that writes, checking the script may write it
+fn writable(state: &State, path: Value) -> Result<PathBuf, InterpreterErrorKind> {
+	let path = PathBuf::from(string(path)?);
+	require(
+		state.config.capabilities.can_write(&path),
+		&format!("write '{}'", path.display()),
+	)?;
+	Ok(path)
+}
+
+/// Turns an IO error into a runtime error naming the path
+fn io_error(path: &Path) -> impl FnOnce(std::io::Error) -> InterpreterErrorKind + '_ {
+	move |e| InterpreterErrorKind::Io(format!("{}: {e}", path.display()))
+}
+
+fn path_value(path: &Path) -> Value { Value::String(path.to_string_lossy().into_owned()) }
+
+/// Counts a value read from the filesystem against `Config::allocation_limit`
+fn allocated(state: &mut State, value: Value) -> Result<Value, InterpreterErrorKind> {
+	state.allocate(value.heap_size())?;
+	Ok(value)
+}
+
+fn read_text(state: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	let [path] = self::arguments(arguments)?;
+	let path = readable(state, path)?;
+	let text = fs::read_to_string(&path).map_err(io_error(&path))?;
+	allocated(state, Value::String(text))
+}
+
+/// The lines of a text file, without their line endings
+fn read_lines(state: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	let [path] = self::arguments(arguments)?;
+	let path = readable(state, path)?;
+	let text = fs::read_to_string(&path).map_err(io_error(&path))?;
+	let lines = text
+		.lines()
+		.map(|line| Value::String(line.to_owned()))
+		.collect();
+	allocated(state, Value::Array(lines))
+}
+
+/// Replaces the contents of a file, creating it if needed
+fn write_text(state: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	let [path, text] = self::arguments(arguments)?;
+	let path = writable(state, path)?;
+	fs::write(&path, string(text)?).map_err(io_error(&path))?;
+	Ok(Value::Null)
+}
+
+/// Adds text to the end of a file, creating it if needed
+fn append(state: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	let [path, text] = self::arguments(arguments)?;
+	let path = writable(state, path)?;
+	let text = string(text)?;
+	OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(&path)
+		.and_then(|mut file| file.write_all(text.as_bytes()))
+		.map_err(io_error(&path))?;
+	Ok(Value::Null)
+}
+
+fn exists(state: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	let [path] = self::arguments(arguments)?;
+	let path = readable(state, path)?;
+	Ok(Value::Bool(path.exists()))
+}
+
+/// Names of the entries of a directory, sorted
+fn list_dir(state: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	let [path] = self::arguments(arguments)?;
+	let path = readable(state, path)?;
+	let mut names = fs::read_dir(&path)
+		.and_then(|entries| {
+			entries
+				.map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+				.collect::<Result<Vec<_>, _>>()
+		})
+		.map_err(io_error(&path))?;
+	names.sort();
+	allocated(
+		state,
+		Value::Array(names.into_iter().map(Value::String).collect()),
+	)
+}
+
+/// Creates a directory and any missing parents
+fn mkdir(state: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	let [path] = self::arguments(arguments)?;
+	let path = writable(state, path)?;
+	fs::create_dir_all(&path).map_err(io_error(&path))?;
+	Ok(Value::Null)
+}
+
+/// Removes a file, or a directory with everything in it
+fn remove(state: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	let [path] = self::arguments(arguments)?;
+	let path = writable(state, path)?;
+	// A link to a directory is removed like a file, leaving the directory alone
+	let metadata = fs::symlink_metadata(&path).map_err(io_error(&path))?;
+	if metadata.is_dir() {
+		fs::remove_dir_all(&path)
+	} else {
+		fs::remove_file(&path)
+	}
+	.map_err(io_error(&path))?;
+	Ok(Value::Null)
+}
+
+/// Sorted paths matching a glob pattern, see `wildcard` for the syntax.
+/// A `**` component matches any number of directories.
+fn glob(state: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	let [pattern] = self::arguments(arguments)?;
+	let pattern = string(pattern)?;
+	let pattern = Path::new(&pattern);
+
+	let mut start = PathBuf::new();
+	let mut components = Vec::new();
+	for component in pattern.components() {
+		match component {
+			Component::Normal(component) => components.push(component.to_string_lossy()),
+			_ if components.is_empty() => start.push(component),
+			// `..` after a wildcard
+			_ => return Err(InterpreterErrorKind::UnsupportedOperation),
+		}
+	}
+	let components = components.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+
+	let mut paths = Vec::new();
+	walk(state, &start, &components, &mut paths)?;
+	paths.sort();
+	paths.dedup();
+	allocated(
+		state,
+		Value::Array(paths.iter().map(|path| path_value(path)).collect()),
+	)
+}
+
+fn walk(
+	state: &mut State,
+	directory: &Path,
+	components: &[&str],
+	paths: &mut Vec<PathBuf>,
+) -> Result<(), InterpreterErrorKind> {
+	let Some((&component, rest)) = components.split_first() else {
+		check_readable(state, directory)?;
+		paths.push(directory.to_owned());
+		return Ok(());
+	};
+	if !component.contains(['*', '?', '[']) {
+		let path = directory.join(component);
+		// Directories on the way to a readable one are passed through without being listed.
+		// Whether a path exists is only revealed if it could be read.
+		let readable = state.config.capabilities.can_read(&path);
+		if !readable || (rest.is_empty() && path.exists()) || path.is_dir() {
+			walk(state, &path, rest, paths)?;
+		}
+		return Ok(());
+	}
+
+	let listed = if directory.as_os_str().is_empty() {
+		Path::new(".")
+	} else {
+		directory
+	};
+	check_readable(state, listed)?;
+	// Globs over big trees can take a while
+	state.step()?;
+	let Ok(entries) = fs::read_dir(listed) else {
+		return Ok(());
+	};
+	if component == "**" {
+		walk(state, directory, rest, paths)?;
+	}
+	for entry in entries.flatten() {
+		let name = entry.file_name().to_string_lossy().into_owned();
+		// Links aren't followed by `**`, so they can't loop
+		let is_dir = entry.file_type().is_ok_and(|kind| kind.is_dir());
+		let path = directory.join(&name);
+		// Links leading out of the readable directories are left out
+		if !state.config.capabilities.can_read(&path) {
+			continue;
+		}
+		if component == "**" {
+			if is_dir && !name.starts_with('.') {
+				walk(state, &path, components, paths)?;
+			}
+		} else if wildcard(component, &name) && (rest.is_empty() || path.is_dir()) {
+			walk(state, &path, rest, paths)?;
+		}
+	}
+	Ok(())
+}
+
+/// Whether a path matches a glob pattern, without looking at the filesystem
+fn matches(_: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	let [pattern, path] = self::arguments(arguments)?;
+	let (pattern, path) = (string(pattern)?, string(path)?);
+	let pattern = pattern.split('/').collect::<Vec<_>>();
+	let path = path.split('/').collect::<Vec<_>>();
+	Ok(Value::Bool(components_match(&pattern, &path)))
+}
+
+fn components_match(pattern: &[&str], path: &[&str]) -> bool {
+	// Whether the pattern so far matches the first `i` components of the path
+	let mut matched = vec![false; path.len() + 1];
+	matched[0] = true;
+	for &component in pattern {
+		let mut next = vec![false; path.len() + 1];
+		for i in 0..=path.len() {
+			next[i] = if component == "**" {
+				// No components, or one more that isn't hidden
+				matched[i] || (i > 0 && next[i - 1] && !path[i - 1].starts_with('.'))
+			} else {
+				i > 0 && matched[i - 1] && wildcard(component, path[i - 1])
+			};
+		}
+		matched = next;
+	}
+	matched[path.len()]
+}
+
+/// Whether a file name matches a pattern, where `*` matches any characters, `?` one character,
+/// and `[abc]`, `[a-z]` or `[!abc]` one of a set of characters.
+/// Wildcards don't match a leading `.`, so hidden files have to be named explicitly.
+fn wildcard(pattern: &str, name: &str) -> bool {
+	if name.starts_with('.') && !pattern.starts_with('.') {
+		return false;
+	}
+	let pieces = pieces(pattern);
+	let name = name.chars().collect::<Vec<_>>();
+
+	let (mut piece, mut c) = (0, 0);
+	// The piece after the last `*`, and where in the name that `*` stops
+	let mut backtrack = None;
+	while c < name.len() {
+		match pieces.get(piece) {
+			Some(Piece::Any) => {
+				piece += 1;
+				backtrack = Some((piece, c));
+			},
+			Some(next) if next.matches(name[c]) => {
+				piece += 1;
+				c += 1;
+			},
+			// The last `*` takes one more character and the rest is tried again
+			_ => match backtrack {
+				Some((after, stop)) => {
+					backtrack = Some((after, stop + 1));
+					(piece, c) = (after, stop + 1);
+				},
+				None => return false,
+			},
+		}
+	}
+	pieces[piece..]
+		.iter()
+		.all(|piece| matches!(piece, Piece::Any))
+}
+
+/// Part of a file name pattern
+enum Piece {
+	/// `*`
+	Any,
+	/// `?`
+	One,
+	/// Ranges of characters, where single characters are ranges of one
+	Set {
+		negated: bool,
+		ranges: Vec<(char, char)>,
+	},
+	Literal(char),
+}
+
+impl Piece {
+	/// Whether a single character matches, which is never the case for `Any`
+	fn matches(&self, c: char) -> bool {
+		match self {
+			Self::Any => false,
+			Self::One => true,
+			Self::Set { negated, ranges } => {
+				let in_set = ranges
+					.iter()
+					.any(|&(first, last)| (first..=last).contains(&c));
+				in_set != *negated
+			},
+			Self::Literal(literal) => *literal == c,
+		}
+	}
+}
+
+fn pieces(pattern: &str) -> Vec<Piece> {
+	let pattern = pattern.chars().collect::<Vec<_>>();
+	let mut pieces = Vec::new();
+	let mut i = 0;
+	while i < pattern.len() {
+		pieces.push(match pattern[i] {
+			'*' => Piece::Any,
+			'?' => Piece::One,
+			'[' => {
+				// A `]` right after the `[` is part of the set
+				let end = pattern[i + 1..]
+					.iter()
+					.skip(1)
+					.position(|&c| c == ']')
+					.map(|end| i + 2 + end);
+				let Some(end) = end else {
+					// An unclosed `[` is matched literally
+					pieces.push(Piece::Literal('['));
+					i += 1;
+					continue;
+				};
+				let (negated, members) = match &pattern[i + 1..end] {
+					['!', members @ ..] => (true, members),
+					members => (false, members),
+				};
+				// A `-` is only a range between two characters, otherwise it's a member
+				let mut ranges = Vec::new();
+				let mut members = members;
+				while let Some(&first) = members.first() {
+					if let ['-', last, rest @ ..] = &members[1..] {
+						ranges.push((first, *last));
+						members = rest;
+					} else {
+						ranges.push((first, first));
+						members = &members[1..];
+					}
+				}
+				let set = Piece::Set { negated, ranges };
+				i = end;
+				set
+			},
+			literal => Piece::Literal(literal),
+		});
+		i += 1;
+	}
+	pieces
+}
+
+/// Joins any number of paths, where an absolute one replaces the ones before it
+fn join(_: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	let mut path = PathBuf::new();
+	for part in arguments {
+		path.push(string(part)?);
+	}
+	Ok(path_value(&path))
+}
+
+/// The components of a path, starting with `/` for absolute paths
+fn split(_: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	let [path] = self::arguments(arguments)?;
+	let path = PathBuf::from(string(path)?);
+	Ok(Value::Array(
+		path.components()
+			.map(|component| path_value(component.as_ref()))
+			.collect(),
+	))
+}
+
+/// Applies a function returning part of a path, giving `null` if the path has no such part
+fn path_part(
+	arguments: Vec<Value>,
+	part: impl FnOnce(&Path) -> Option<&Path>,
+) -> Result<Value, InterpreterErrorKind> {
+	let [path] = self::arguments(arguments)?;
+	let path = PathBuf::from(string(path)?);
+	Ok(part(&path).map_or(Value::Null, path_value))
+}
+
+fn parent(_: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	path_part(arguments, Path::parent)
+}
+
+fn file_name(_: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	path_part(arguments, |path| path.file_name().map(Path::new))
+}
+
+/// The file name without its extension
+fn stem(_: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	path_part(arguments, |path| path.file_stem().map(Path::new))
+}
+
+/// The extension of the file name, without the `.`
+fn extension(_: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	path_part(arguments, |path| path.extension().map(Path::new))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+
+	use super::wildcard;
+	use crate::interpreter::{
+		error::InterpreterErrorKind,
+		interpret::tests::{array, directory, evaluate_with_config, string},
+	};
+
+	#[test]
+	fn absolute_globs_under_the_readable_root() {
+		let (directory, config) = directory("glob");
+		fs::create_dir(directory.join("data")).unwrap();
+		fs::write(directory.join("data/a.json"), "").unwrap();
+		fs::write(directory.join("data/b.txt"), "").unwrap();
+		fs::write(directory.join("data/.hidden.json"), "").unwrap();
+		let glob = |pattern: &str| {
+			let source = format!(
+				r#"import "std/fs" as fs; let result = fs.glob("{}/{pattern}");"#,
+				directory.display()
+			);
+			evaluate_with_config(&source, config.clone())
+		};
+		let found = |path: &str| string(&directory.join(path).display().to_string());
+		assert_eq!(glob("*/*.json").unwrap(), array([found("data/a.json")]));
+		assert_eq!(glob("**/b.txt").unwrap(), array([found("data/b.txt")]));
+		assert_eq!(glob("data/a.json").unwrap(), array([found("data/a.json")]));
+		assert_eq!(glob("missing/*").unwrap(), array([]));
+
+		// Outside of the root, nothing is revealed about what exists
+		let outside = directory.parent().unwrap().display().to_string();
+		for pattern in ["*", "missing/*", "missing.json"] {
+			let source = format!(r#"import "std/fs" as fs; fs.glob("{outside}/{pattern}");"#);
+			assert!(
+				matches!(
+					evaluate_with_config(&source, config.clone()),
+					Err(InterpreterErrorKind::PermissionDenied(_))
+				),
+				"{pattern}"
+			);
+		}
+		fs::remove_dir_all(directory).unwrap();
+	}
+
+	#[test]
+	fn set_ranges() {
+		assert!(wildcard("[a-c]", "b"));
+		assert!(!wildcard("[a-c]", "-"));
+		assert!(!wildcard("[a-c]", "d"));
+		assert!(wildcard("[!a-c]", "-"));
+		// A `-` at either end of a set is a member
+		assert!(wildcard("[-a]", "-"));
+		assert!(wildcard("[a-]", "-"));
+		assert!(wildcard("[a-cx-z]", "y"));
+	}
+
+	#[test]
+	fn stars_backtrack_in_linear_steps() {
+		assert!(!wildcard("*a*a*a*a*a*a*a*a*a*b", &"a".repeat(36)));
+		assert!(wildcard(
+			"*a*a*a*a*a*a*a*a*a*b",
+			&format!("{}b", "a".repeat(36))
+		));
+	}
+}
@@ -0,0 +1,453 @@
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{
+	ast::{Float, Integer},
+	interpreter::{
+		error::InterpreterErrorKind,
+		state::State,
+		stdlib::{
+			arguments,
+			cursor::{encode_error, write_string, Cursor, MAX_DEPTH},
+			option, optional_arguments, options, string,
+		},
+		value::{HashableValue, NativeFunction, Value},
+	},
+};
+
+pub const FUNCTIONS: &[NativeFunction] = &[
+	NativeFunction {
+		name: "json.parse",
+		function: parse,
+	},
+	NativeFunction {
+		name: "json.stringify",
+		function: stringify,
+	},
+];
+
+/// Decodes JSON text. Numbers without a fraction or exponent become integers
+/// if they fit, every other number becomes a float.
+fn parse(state: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	let [text] = self::arguments(arguments)?;
+	let text = string(text)?;
+	let mut parser = Parser {
+		cursor: Cursor::new(&text, "JSON"),
+		depth: 0,
+	};
+	parser.skip_whitespace();
+	let value = parser.parse_value()?;
+	parser.skip_whitespace();
+	if parser.cursor.peek().is_some() {
+		return Err(parser.cursor.error("trailing characters after the value"));
+	}
+	state.allocate(value.heap_size())?;
+	Ok(value)
+}
+
+/// Encodes a value as JSON, with object keys sorted.
+/// Takes an optional map of options, where `"indent"` is the number of spaces to indent with.
+fn stringify(state: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	let ([value], options) = optional_arguments(arguments)?;
+	let indent = match option(&mut self::options(options)?, "indent") {
+		None => None,
+		Some(Value::Integer(indent)) => {
+			Some(usize::try_from(indent).map_err(|_| InterpreterErrorKind::UnsupportedOperation)?)
+		},
+		Some(_) => return Err(InterpreterErrorKind::UnsupportedOperation),
+	};
+
+	let mut output = String::new();
+	write_value(&mut output, &value, indent, 0)?;
+	state.allocate(output.len())?;
+	Ok(Value::String(output))
+}
+
+fn write_value(
+	output: &mut String,
+	value: &Value,
+	indent: Option<usize>,
+	depth: usize,
+) -> Result<(), InterpreterErrorKind> {
+	match value {
+		Value::Null => output.push_str("null"),
+		Value::Bool(bool) => write!(output, "{bool}").unwrap(),
+		Value::Integer(integer) => write!(output, "{integer}").unwrap(),
+		// Debug formatting keeps the `.0` of whole floats, so they decode as floats again
+		Value::Float(float) if float.is_finite() => write!(output, "{float:?}").unwrap(),
+		Value::Float(float) => {
+			return Err(encode_error("JSON", format!("{float} isn't a JSON number")))
+		},
+		Value::String(string) => write_string(output, string),
+		Value::Array(_) | Value::Map(_) if depth == MAX_DEPTH => {
+			return Err(encode_error("JSON", "arrays and maps nested too deeply"))
+		},
+		Value::Array(array) => {
+			if array.is_empty() {
+				output.push_str("[]");
+				return Ok(());
+			}
+			output.push('[');
+			for (i, element) in array.iter().enumerate() {
+				if i > 0 {
+					output.push(',');
+				}
+				write_newline(output, indent, depth + 1);
+				write_value(output, element, indent, depth + 1)?;
+			}
+			write_newline(output, indent, depth);
+			output.push(']');
+		},
+		Value::Map(map) => {
+			if map.is_empty() {
+				output.push_str("{}");
+				return Ok(());
+			}
+			let mut entries = map
+				.iter()
+				.map(|(key, value)| match key {
+					HashableValue::String(key) => Ok((key, value)),
+					key => Err(encode_error(
+						"JSON",
+						format!("map key {:?} isn't a string", Value::from(key.clone())),
+					)),
+				})
+				.collect::<Result<Vec<_>, _>>()?;
+			entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+
+			output.push('{');
+			for (i, (key, value)) in entries.into_iter().enumerate() {
+				if i > 0 {
+					output.push(',');
+				}
+				write_newline(output, indent, depth + 1);
+				write_string(output, key);
+				output.push_str(if indent.is_some() { ": " } else { ":" });
+				write_value(output, value, indent, depth + 1)?;
+			}
+			write_newline(output, indent, depth);
+			output.push('}');
+		},
+		Value::Range(_) => {
+			return Err(encode_error(
+				"JSON",
+				"ranges can't be encoded, spread them into an array",
+			))
+		},
+		Value::Function(..) | Value::NativeFunction(_) => {
+			return Err(encode_error("JSON", "functions can't be encoded"))
+		},
+		Value::Generator(_) => return Err(encode_error("JSON", "generators can't be encoded")),
+	}
+	Ok(())
+}
+
+fn write_newline(output: &mut String, indent: Option<usize>, depth: usize) {
+	if let Some(indent) = indent {
+		output.push('\n');
+		output.extend(std::iter::repeat_n(' ', indent * depth));
+	}
+}
+
+struct Parser<'a> {
+	cursor: Cursor<'a>,
+	/// Arrays and objects currently open
+	depth: usize,
+}
+
+impl Parser<'_> {
+	fn skip_whitespace(&mut self) {
+		while self
+			.cursor
+			.peek()
+			.is_some_and(|c| matches!(c, ' ' | '\t' | '\n' | '\r'))
+		{
+			self.cursor.next();
+		}
+	}
+
+	fn parse_value(&mut self) -> Result<Value, InterpreterErrorKind> {
+		match self.cursor.peek() {
+			Some('n') => self.parse_keyword("null", Value::Null),
+			Some('t') => self.parse_keyword("true", Value::Bool(true)),
+			Some('f') => self.parse_keyword("false", Value::Bool(false)),
+			Some('"') => Ok(Value::String(self.parse_string()?)),
+			Some('[') => self.nested(Self::parse_array),
+			Some('{') => self.nested(Self::parse_object),
+			Some('-' | '0'..='9') => self.parse_number(),
+			Some(c) => Err(self.cursor.error(format!("unexpected character '{c}'"))),
+			None => Err(self
+				.cursor
+				.error("expected a value, found the end of the text")),
+		}
+	}
+
+	fn parse_keyword(
+		&mut self,
+		keyword: &str,
+		value: Value,
+	) -> Result<Value, InterpreterErrorKind> {
+		for expected in keyword.chars() {
+			if !self.cursor.eat(expected) {
+				return Err(self
+					.cursor
+					.error(format!("invalid literal, expected '{keyword}'")));
+			}
+		}
+		Ok(value)
+	}
+
+	fn nested(
+		&mut self,
+		parse: fn(&mut Self) -> Result<Value, InterpreterErrorKind>,
+	) -> Result<Value, InterpreterErrorKind> {
+		if self.depth == MAX_DEPTH {
+			return Err(self.cursor.error("arrays and objects nested too deeply"));
+		}
+		self.depth += 1;
+		let value = parse(self)?;
+		self.depth -= 1;
+		Ok(value)
+	}
+
+	fn parse_array(&mut self) -> Result<Value, InterpreterErrorKind> {
+		self.cursor.expect('[')?;
+		let mut array = Vec::new();
+		self.skip_whitespace();
+		if self.cursor.eat(']') {
+			return Ok(Value::Array(array));
+		}
+		loop {
+			self.skip_whitespace();
+			array.push(self.parse_value()?);
+			self.skip_whitespace();
+			match self.cursor.next() {
+				Some(',') => {},
+				Some(']') => return Ok(Value::Array(array)),
+				_ => {
+					return Err(self
+						.cursor
+						.error("expected ',' or ']' after an array element"))
+				},
+			}
+		}
+	}
+
+	fn parse_object(&mut self) -> Result<Value, InterpreterErrorKind> {
+		self.cursor.expect('{')?;
+		let mut map = HashMap::new();
+		self.skip_whitespace();
+		if self.cursor.eat('}') {
+			return Ok(Value::Map(map));
+		}
+		loop {
+			self.skip_whitespace();
+			if self.cursor.peek() != Some('"') {
+				return Err(self.cursor.error("expected a string key"));
+			}
+			let key = self.parse_string()?;
+			self.skip_whitespace();
+			self.cursor.expect(':')?;
+			self.skip_whitespace();
+			map.insert(HashableValue::String(key), self.parse_value()?);
+			self.skip_whitespace();
+			match self.cursor.next() {
+				Some(',') => {},
+				Some('}') => return Ok(Value::Map(map)),
+				_ => {
+					return Err(self
+						.cursor
+						.error("expected ',' or '}' after an object entry"))
+				},
+			}
+		}
+	}
+
+	fn parse_string(&mut self) -> Result<String, InterpreterErrorKind> {
+		self.cursor.expect('"')?;
+		let mut string = String::new();
+		loop {
+			match self.cursor.next() {
+				Some('"') => return Ok(string),
+				Some('\\') => {
+					let c = match self.cursor.next() {
+						Some('"') => '"',
+						Some('\\') => '\\',
+						Some('/') => '/',
+						Some('b') => '\u{8}',
+						Some('f') => '\u{c}',
+						Some('n') => '\n',
+						Some('r') => '\r',
+						Some('t') => '\t',
+						Some('u') => self.parse_unicode_escape()?,
+						_ => return Err(self.cursor.error("invalid escape sequence")),
+					};
+					string.push(c);
+				},
+				Some(c) if c.is_control() => {
+					return Err(self.cursor.error("control character in a string"))
+				},
+				Some(c) => string.push(c),
+				None => return Err(self.cursor.error("unterminated string")),
+			}
+		}
+	}
+
+	/// The character of a `\u` escape, which may be followed by a second one for a surrogate pair
+	fn parse_unicode_escape(&mut self) -> Result<char, InterpreterErrorKind> {
+		let first = self.parse_hex()?;
+		let code = if (0xD800..0xDC00).contains(&first) {
+			if self.cursor.next() != Some('\\') || self.cursor.next() != Some('u') {
+				return Err(self.cursor.error("unpaired surrogate in a unicode escape"));
+			}
+			let second = self.parse_hex()?;
+			if !(0xDC00..0xE000).contains(&second) {
+				return Err(self.cursor.error("unpaired surrogate in a unicode escape"));
+			}
+			0x10000 + ((first - 0xD800) << 10) + (second - 0xDC00)
+		} else {
+			first
+		};
+		char::from_u32(code)
+			.ok_or_else(|| self.cursor.error("unpaired surrogate in a unicode escape"))
+	}
+
+	fn parse_hex(&mut self) -> Result<u32, InterpreterErrorKind> {
+		let mut code = 0;
+		for _ in 0..4 {
+			let Some(digit) = self.cursor.next().and_then(|c| c.to_digit(16)) else {
+				return Err(self
+					.cursor
+					.error("expected 4 hex digits in a unicode escape"));
+			};
+			code = code * 16 + digit;
+		}
+		Ok(code)
+	}
+
+	fn parse_number(&mut self) -> Result<Value, InterpreterErrorKind> {
+		let mut number = String::new();
+		let mut is_float = false;
+		if self.cursor.peek() == Some('-') {
+			number.extend(self.cursor.next());
+		}
+		match self.cursor.peek() {
+			Some('0') => number.extend(self.cursor.next()),
+			Some('1'..='9') => self.take_digits(&mut number),
+			_ => return Err(self.cursor.error("expected a digit")),
+		}
+		if self.cursor.peek() == Some('.') {
+			is_float = true;
+			number.extend(self.cursor.next());
+			if !self.cursor.peek().is_some_and(|c| c.is_ascii_digit()) {
+				return Err(self
+					.cursor
+					.error("expected a digit after the decimal point"));
+			}
+			self.take_digits(&mut number);
+		}
+		if matches!(self.cursor.peek(), Some('e' | 'E')) {
+			is_float = true;
+			number.extend(self.cursor.next());
+			if matches!(self.cursor.peek(), Some('+' | '-')) {
+				number.extend(self.cursor.next());
+			}
+			if !self.cursor.peek().is_some_and(|c| c.is_ascii_digit()) {
+				return Err(self.cursor.error("expected a digit in the exponent"));
+			}
+			self.take_digits(&mut number);
+		}
+
+		if !is_float {
+			if let Ok(integer) = number.parse::<Integer>() {
+				return Ok(Value::Integer(integer));
+			}
+		}
+		// Integers outside of the integer range lose precision as floats
+		match number.parse::<Float>() {
+			Ok(float) if float.is_finite() => Ok(Value::Float(float)),
+			Ok(_) => Err(self.cursor.error("number too large")),
+			Err(e) => Err(self.cursor.error(e.to_string())),
+		}
+	}
+
+	fn take_digits(&mut self, number: &mut String) {
+		while self.cursor.peek().is_some_and(|c| c.is_ascii_digit()) {
+			number.extend(self.cursor.next());
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::interpreter::{
+		error::InterpreterErrorKind,
+		interpret::tests::{array, evaluate, string},
+		stdlib::cursor::MAX_DEPTH,
+		value::Value,
+	};
+
+	fn json(call: &str) -> Result<Value, InterpreterErrorKind> {
+		evaluate(&format!(
+			r#"import "std/json" as json; let result = {call};"#
+		))
+	}
+
+	#[test]
+	fn round_trip() {
+		let value = r#"{"b": [1, 2.5, -3], "a": {"c": null, "d": true}, "e": "x"}"#;
+		assert_eq!(
+			json(&format!(
+				"json.stringify(json.parse(json.stringify({value})))"
+			))
+			.unwrap(),
+			string(r#"{"a":{"c":null,"d":true},"b":[1,2.5,-3],"e":"x"}"#)
+		);
+		assert_eq!(
+			json(r#"json.stringify([1, {"a": []}], {"indent": 2})"#).unwrap(),
+			string("[\n  1,\n  {\n    \"a\": []\n  }\n]")
+		);
+		assert_eq!(
+			json(r#"json.parse("[1e3, 3, 10000000000]")"#).unwrap(),
+			array([Value::Float(1000.0), Value::Integer(3), Value::Float(1e10)])
+		);
+	}
+
+	#[test]
+	fn errors_have_a_location() {
+		let Err(InterpreterErrorKind::Decode { line, column, .. }) =
+			json("json.parse(\"[1,\n  2,,]\")")
+		else {
+			panic!("expected a decode error");
+		};
+		assert_eq!((line, column), (2, 5));
+		assert!(matches!(
+			json(r#"json.parse("1e400")"#),
+			Err(InterpreterErrorKind::Decode { .. })
+		));
+		assert!(matches!(
+			json("json.stringify({1: 2})"),
+			Err(InterpreterErrorKind::Encode { .. })
+		));
+	}
+
+	#[test]
+	fn nesting_is_limited() {
+		let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+		assert!(json(&format!(r#"json.parse("{}")"#, nested(MAX_DEPTH))).is_ok());
+		assert!(matches!(
+			json(&format!(r#"json.parse("{}")"#, nested(MAX_DEPTH + 1))),
+			Err(InterpreterErrorKind::Decode { .. })
+		));
+		let build = |depth: usize| {
+			format!(
+				"let a = []; for i in 0..{} {{ a = [a]; }} json.stringify(a)",
+				depth - 1
+			)
+		};
+		assert!(json(&format!("{{ {} }}", build(MAX_DEPTH))).is_ok());
+		assert!(matches!(
+			json(&format!("{{ {} }}", build(1000))),
+			Err(InterpreterErrorKind::Encode { .. })
+		));
+	}
+}
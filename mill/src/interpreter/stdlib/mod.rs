@@ -0,0 +1,181 @@
+//! Modules built into the interpreter, imported with paths like `"std/env"`.
+//! Each one is behind a cargo feature of the same name.
+
+#[cfg(feature = "clock")]
+mod clock;
+#[cfg(feature = "csv")]
+mod csv;
+#[cfg(any(feature = "json", feature = "toml", feature = "csv"))]
+mod cursor;
+#[cfg(feature = "env")]
+mod env;
+#[cfg(feature = "fs")]
+mod fs;
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "process")]
+mod process;
+#[cfg(feature = "random")]
+mod random;
+#[cfg(feature = "toml")]
+mod toml;
+
+use std::collections::HashMap;
+
+use crate::interpreter::{
+	error::InterpreterErrorKind,
+	value::{HashableValue, NativeFunction, Value},
+};
+
+/// Start of the import paths of built-in modules
+pub const PREFIX: &str = "std/";
+
+/// Map of the functions of a built-in module, named `module.function`
+pub fn module(name: &str) -> Option<Value> {
+	let functions: &[NativeFunction] = match name {
+		#[cfg(feature = "clock")]
+		"clock" => clock::FUNCTIONS,
+		#[cfg(feature = "csv")]
+		"csv" => csv::FUNCTIONS,
+		#[cfg(feature = "env")]
+		"env" => env::FUNCTIONS,
+		#[cfg(feature = "fs")]
+		"fs" => fs::FUNCTIONS,
+		#[cfg(feature = "json")]
+		"json" => json::FUNCTIONS,
+		#[cfg(feature = "process")]
+		"process" => process::FUNCTIONS,
+		#[cfg(feature = "random")]
+		"random" => random::FUNCTIONS,
+		#[cfg(feature = "toml")]
+		"toml" => toml::FUNCTIONS,
+		_ => return None,
+	};
+	Some(Value::Map(
+		functions
+			.iter()
+			.map(|&function| {
+				let (_, name) = function.name.split_once('.').unwrap_or(("", function.name));
+				(
+					HashableValue::String(name.to_owned()),
+					Value::NativeFunction(function),
+				)
+			})
+			.collect(),
+	))
+}
+
+/// Fails with `PermissionDenied` unless the capability is given
+fn require(allowed: bool, action: &str) -> Result<(), InterpreterErrorKind> {
+	if allowed {
+		Ok(())
+	} else {
+		Err(InterpreterErrorKind::PermissionDenied(action.to_owned()))
+	}
+}
+
+fn arguments<const N: usize>(arguments: Vec<Value>) -> Result<[Value; N], InterpreterErrorKind> {
+	<[Value; N]>::try_from(arguments).map_err(|_| InterpreterErrorKind::WrongArgumentCount)
+}
+
+/// Splits off the optional last argument of a function with `N` other arguments
+fn optional_arguments<const N: usize>(
+	mut arguments: Vec<Value>,
+) -> Result<([Value; N], Option<Value>), InterpreterErrorKind> {
+	let optional = if arguments.len() > N {
+		arguments.pop()
+	} else {
+		None
+	};
+	Ok((self::arguments(arguments)?, optional))
+}
+
+/// The entries of an optional map of options
+fn options(options: Option<Value>) -> Result<HashMap<HashableValue, Value>, InterpreterErrorKind> {
+	match options {
+		None => Ok(HashMap::new()),
+		Some(Value::Map(options)) => Ok(options),
+		Some(_) => Err(InterpreterErrorKind::ExpectedMap),
+	}
+}
+
+/// Takes an option out of a map of options, where `null` counts as not given
+fn option(options: &mut HashMap<HashableValue, Value>, name: &str) -> Option<Value> {
+	options
+		.remove(&HashableValue::String(name.to_owned()))
+		.filter(|value| !matches!(value, Value::Null))
+}
+
+fn string(value: Value) -> Result<String, InterpreterErrorKind> {
+	match value {
+		Value::String(string) => Ok(string),
+		_ => Err(InterpreterErrorKind::ExpectedString),
+	}
+}
+
+#[cfg(test)]
+#[cfg(all(
+	feature = "fs",
+	feature = "env",
+	feature = "process",
+	feature = "clock",
+	feature = "random"
+))]
+mod tests {
+	use crate::interpreter::{
+		config::{Capabilities, Config},
+		error::InterpreterErrorKind,
+		interpret::tests::{evaluate, evaluate_with_config},
+		value::Value,
+	};
+
+	#[test]
+	fn side_effects_need_capabilities() {
+		for (module, call) in [
+			("fs", r#"fs.read_text("Cargo.toml")"#),
+			("fs", r#"fs.write_text("written.txt", "")"#),
+			("fs", r#"fs.glob("*")"#),
+			("env", r#"env.get("HOME")"#),
+			("process", r#"process.run("true", [])"#),
+			("clock", "clock.now()"),
+			("random", "random.float()"),
+		] {
+			let source = format!(r#"import "std/{module}" as {module}; {call};"#);
+			assert!(
+				matches!(
+					evaluate(&source),
+					Err(InterpreterErrorKind::PermissionDenied(_))
+				),
+				"{call}"
+			);
+		}
+		// Functions without side effects need nothing
+		assert_eq!(
+			evaluate(r#"import "std/fs" as fs; let result = fs.extension("a.txt");"#).unwrap(),
+			Value::String("txt".to_owned())
+		);
+	}
+
+	#[test]
+	fn random_int_needs_a_non_empty_range() {
+		let config = Config {
+			capabilities: Capabilities {
+				random: true,
+				..Capabilities::default()
+			},
+			..Config::default()
+		};
+		let source = r#"import "std/random" as random; let result = random.int(3, 4);"#;
+		assert_eq!(
+			evaluate_with_config(source, config.clone()).unwrap(),
+			Value::Integer(3)
+		);
+		for (start, end) in [(3, 3), (4, 3)] {
+			let source = format!(r#"import "std/random" as random; random.int({start}, {end});"#);
+			assert!(matches!(
+				evaluate_with_config(&source, config.clone()),
+				Err(InterpreterErrorKind::EmptyRange)
+			));
+		}
+	}
+}
@@ -0,0 +1,51 @@
+use std::{collections::HashMap, process::Command};
+
+use crate::{
+	ast::Integer,
+	interpreter::{
+		error::InterpreterErrorKind,
+		state::State,
+		stdlib::{optional_arguments, require, string},
+		value::{HashableValue, NativeFunction, Value},
+	},
+};
+
+pub const FUNCTIONS: &[NativeFunction] = &[NativeFunction {
+	name: "process.run",
+	function: run,
+}];
+
+/// Runs a program with an optional array of arguments and waits for it to finish.
+/// Returns its exit status, which is `null` if it was killed, and its output.
+fn run(state: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	require(state.config.capabilities.process, "run programs")?;
+	let ([program], program_arguments) = optional_arguments(arguments)?;
+	let program_arguments = match program_arguments {
+		Some(Value::Array(program_arguments)) => program_arguments
+			.into_iter()
+			.map(string)
+			.collect::<Result<Vec<_>, _>>()?,
+		Some(_) => return Err(InterpreterErrorKind::ExpectedArray),
+		None => Vec::new(),
+	};
+
+	let output = Command::new(string(program)?)
+		.args(program_arguments)
+		.output()
+		.map_err(|e| InterpreterErrorKind::Io(e.to_string()))?;
+	let status = output
+		.status
+		.code()
+		.map_or(Value::Null, |code| Value::Integer(Integer::from(code)));
+	Ok(Value::Map(HashMap::from([
+		(HashableValue::String("status".to_owned()), status),
+		(
+			HashableValue::String("stdout".to_owned()),
+			Value::String(String::from_utf8_lossy(&output.stdout).into_owned()),
+		),
+		(
+			HashableValue::String("stderr".to_owned()),
+			Value::String(String::from_utf8_lossy(&output.stderr).into_owned()),
+		),
+	])))
+}
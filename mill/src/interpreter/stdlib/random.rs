@@ -0,0 +1,81 @@
+use std::hash::{BuildHasher, RandomState};
+
+use crate::{
+	ast::{Float, Integer},
+	interpreter::{
+		error::InterpreterErrorKind,
+		state::State,
+		stdlib::{arguments, require},
+		value::{NativeFunction, Value},
+	},
+};
+
+pub const FUNCTIONS: &[NativeFunction] = &[
+	NativeFunction {
+		name: "random.float",
+		function: float,
+	},
+	NativeFunction {
+		name: "random.int",
+		function: int,
+	},
+	NativeFunction {
+		name: "random.choice",
+		function: choice,
+	},
+];
+
+/// Next number of a xorshift64* generator, seeded from the host's hash randomness.
+/// Not suitable for anything security related.
+fn next(state: &mut State) -> Result<u64, InterpreterErrorKind> {
+	require(state.config.capabilities.random, "generate random numbers")?;
+	if state.random == 0 {
+		// Zero would be a fixed point of the generator
+		state.random = RandomState::new().hash_one(0) | 1;
+	}
+	state.random ^= state.random >> 12;
+	state.random ^= state.random << 25;
+	state.random ^= state.random >> 27;
+	Ok(state.random.wrapping_mul(0x2545_F491_4F6C_DD1D))
+}
+
+/// A number below `bound`, which must not be zero
+fn below(state: &mut State, bound: u64) -> Result<u64, InterpreterErrorKind> {
+	Ok(next(state)? % bound)
+}
+
+/// A float in `[0, 1)`
+fn float(state: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	let [] = self::arguments(arguments)?;
+	// The top 53 bits fill the mantissa exactly
+	#[allow(clippy::cast_precision_loss)]
+	let value = (next(state)? >> 11) as Float / (1_u64 << 53) as Float;
+	Ok(Value::Float(value))
+}
+
+/// An integer from `start` up to but not including `end`, like the range `start..end`
+fn int(state: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	let [Value::Integer(start), Value::Integer(end)] = self::arguments(arguments)? else {
+		return Err(InterpreterErrorKind::UnsupportedOperation);
+	};
+	let span = u64::try_from(i64::from(end) - i64::from(start)).unwrap_or(0);
+	if span == 0 {
+		return Err(InterpreterErrorKind::EmptyRange);
+	}
+	let offset = i64::try_from(below(state, span)?).unwrap();
+	Ok(Value::Integer(
+		Integer::try_from(i64::from(start) + offset).unwrap(),
+	))
+}
+
+/// A random element of an array, or `null` if it's empty
+fn choice(state: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	let [Value::Array(mut array)] = self::arguments(arguments)? else {
+		return Err(InterpreterErrorKind::ExpectedArray);
+	};
+	if array.is_empty() {
+		return Ok(Value::Null);
+	}
+	let index = usize::try_from(below(state, array.len() as u64)?).unwrap();
+	Ok(array.swap_remove(index))
+}
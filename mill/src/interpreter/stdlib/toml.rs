@@ -0,0 +1,723 @@
+use std::{
+	collections::{HashMap, HashSet},
+	fmt::Write,
+	iter,
+};
+
+use crate::{
+	ast::{Float, Integer},
+	interpreter::{
+		error::InterpreterErrorKind,
+		state::State,
+		stdlib::{
+			arguments,
+			cursor::{encode_error, write_string, Cursor, MAX_DEPTH},
+			string,
+		},
+		value::{HashableValue, NativeFunction, Value},
+	},
+};
+
+pub const FUNCTIONS: &[NativeFunction] = &[
+	NativeFunction {
+		name: "toml.parse",
+		function: parse,
+	},
+	NativeFunction {
+		name: "toml.stringify",
+		function: stringify,
+	},
+];
+
+type Table = HashMap<HashableValue, Value>;
+
+/// Decodes a TOML document into a map. Dates and times are kept as strings,
+/// and integers outside of the integer range become floats.
+/// Since there's no date type, `toml.stringify` writes dates back as quoted strings.
+fn parse(state: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	let [text] = self::arguments(arguments)?;
+	let text = string(text)?;
+	let mut parser = Parser {
+		cursor: Cursor::new(&text, "TOML"),
+		depth: 0,
+	};
+	let value = Value::Map(parser.parse_document()?);
+	state.allocate(value.heap_size())?;
+	Ok(value)
+}
+
+/// Encodes a map as a TOML document, with keys sorted.
+/// Maps become tables and arrays of maps become arrays of tables, except inside other arrays.
+/// Strings are always quoted, even if they hold a date or time.
+fn stringify(state: &mut State, arguments: Vec<Value>) -> Result<Value, InterpreterErrorKind> {
+	let [value] = self::arguments(arguments)?;
+	let Value::Map(table) = value else {
+		return Err(encode_error(
+			"TOML",
+			"only maps can be encoded as a document",
+		));
+	};
+	let mut output = String::new();
+	write_table(&mut output, &mut Vec::new(), &table)?;
+	state.allocate(output.len())?;
+	Ok(Value::String(output))
+}
+
+/// Writes the keys of a table, followed by its tables and arrays of tables under their headers
+fn write_table<'a>(
+	output: &mut String,
+	path: &mut Vec<&'a str>,
+	table: &'a Table,
+) -> Result<(), InterpreterErrorKind> {
+	let mut entries = table
+		.iter()
+		.map(|(key, value)| match key {
+			HashableValue::String(key) => Ok((key.as_str(), value)),
+			key => Err(encode_error(
+				"TOML",
+				format!("map key {:?} isn't a string", Value::from(key.clone())),
+			)),
+		})
+		.collect::<Result<Vec<_>, _>>()?;
+	entries.sort_unstable_by_key(|&(key, _)| key);
+	let (nested, values): (Vec<_>, Vec<_>) = entries
+		.into_iter()
+		.partition(|(_, value)| matches!(value, Value::Map(_)) || is_table_array(value));
+
+	for (key, value) in values {
+		write_key(output, key);
+		output.push_str(" = ");
+		write_inline(output, value, path.len())?;
+		output.push('\n');
+	}
+	for (key, value) in nested {
+		if path.len() == MAX_DEPTH {
+			return Err(nested_too_deeply());
+		}
+		path.push(key);
+		match value {
+			Value::Map(table) => {
+				write_header(output, path, false);
+				write_table(output, path, table)?;
+			},
+			Value::Array(array) => {
+				for table in array {
+					let Value::Map(table) = table else {
+						unreachable!("arrays of tables only hold maps")
+					};
+					write_header(output, path, true);
+					write_table(output, path, table)?;
+				}
+			},
+			_ => unreachable!("only tables and arrays of tables are nested"),
+		}
+		path.pop();
+	}
+	Ok(())
+}
+
+fn is_table_array(value: &Value) -> bool {
+	matches!(value, Value::Array(array)
+		if !array.is_empty() && array.iter().all(|value| matches!(value, Value::Map(_))))
+}
+
+fn write_header(output: &mut String, path: &[&str], array: bool) {
+	if !output.is_empty() {
+		output.push('\n');
+	}
+	output.push_str(if array { "[[" } else { "[" });
+	for (i, key) in path.iter().enumerate() {
+		if i > 0 {
+			output.push('.');
+		}
+		write_key(output, key);
+	}
+	output.push_str(if array { "]]\n" } else { "]\n" });
+}
+
+fn write_key(output: &mut String, key: &str) {
+	if !key.is_empty() && key.chars().all(is_bare_key) {
+		output.push_str(key);
+	} else {
+		write_string(output, key);
+	}
+}
+
+fn nested_too_deeply() -> InterpreterErrorKind {
+	encode_error("TOML", "arrays and maps nested too deeply")
+}
+
+/// Writes a value on a single line, with maps as inline tables.
+/// `depth` counts the tables and arrays the value is in, besides the document.
+fn write_inline(
+	output: &mut String,
+	value: &Value,
+	depth: usize,
+) -> Result<(), InterpreterErrorKind> {
+	match value {
+		Value::Bool(bool) => write!(output, "{bool}").unwrap(),
+		Value::Integer(integer) => write!(output, "{integer}").unwrap(),
+		Value::Float(float) if float.is_nan() => output.push_str("nan"),
+		Value::Float(float) if float.is_infinite() => {
+			output.push_str(if *float > 0.0 { "inf" } else { "-inf" });
+		},
+		// Debug formatting keeps the `.0` of whole floats, so they decode as floats again
+		Value::Float(float) => write!(output, "{float:?}").unwrap(),
+		Value::String(string) => write_string(output, string),
+		Value::Array(_) | Value::Map(_) if depth == MAX_DEPTH => return Err(nested_too_deeply()),
+		Value::Array(array) => {
+			output.push('[');
+			for (i, element) in array.iter().enumerate() {
+				if i > 0 {
+					output.push_str(", ");
+				}
+				write_inline(output, element, depth + 1)?;
+			}
+			output.push(']');
+		},
+		Value::Map(table) => {
+			let mut entries = table.iter().collect::<Vec<_>>();
+			entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+			output.push('{');
+			for (i, (key, value)) in entries.into_iter().enumerate() {
+				let HashableValue::String(key) = key else {
+					return Err(encode_error(
+						"TOML",
+						format!("map key {:?} isn't a string", Value::from(key.clone())),
+					));
+				};
+				output.push_str(if i > 0 { ", " } else { " " });
+				write_key(output, key);
+				output.push_str(" = ");
+				write_inline(output, value, depth + 1)?;
+			}
+			output.push_str(if table.is_empty() { "}" } else { " }" });
+		},
+		Value::Null => return Err(encode_error("TOML", "TOML has no null")),
+		Value::Range(_) => {
+			return Err(encode_error(
+				"TOML",
+				"ranges can't be encoded, spread them into an array",
+			))
+		},
+		Value::Function(..) | Value::NativeFunction(_) => {
+			return Err(encode_error("TOML", "functions can't be encoded"))
+		},
+		Value::Generator(_) => return Err(encode_error("TOML", "generators can't be encoded")),
+	}
+	Ok(())
+}
+
+fn is_bare_key(c: char) -> bool { c.is_ascii_alphanumeric() || matches!(c, '_' | '-') }
+
+struct Parser<'a> {
+	cursor: Cursor<'a>,
+	/// Arrays and inline tables currently open
+	depth: usize,
+}
+
+impl Parser<'_> {
+	fn parse_document(&mut self) -> Result<Table, InterpreterErrorKind> {
+		let mut root = Table::new();
+		// Path of the table the keys are put in
+		let mut current = Vec::new();
+		// Tables defined by a header, which can't be defined again
+		let mut defined = HashSet::new();
+		loop {
+			self.skip_blank();
+			match self.cursor.peek() {
+				None => return Ok(root),
+				Some('[') => {
+					self.cursor.next();
+					let array = self.cursor.eat('[');
+					self.skip_spaces();
+					let path = self.parse_key()?;
+					self.cursor.expect(']')?;
+					if array {
+						self.cursor.expect(']')?;
+						let (last, parent) = path.split_last().unwrap();
+						let parent = self.table(&mut root, parent)?;
+						match parent
+							.entry(HashableValue::String(last.clone()))
+							.or_insert_with(|| Value::Array(Vec::new()))
+						{
+							Value::Array(array)
+								if array.iter().all(|value| matches!(value, Value::Map(_))) =>
+							{
+								array.push(Value::Map(Table::new()));
+							},
+							_ => {
+								return Err(self
+									.cursor
+									.error(format!("key '{last}' isn't an array of tables")))
+							},
+						}
+						// The tables of the previous element can be defined again in the new one
+						defined.retain(|table: &Vec<String>| !table.starts_with(&path));
+					} else {
+						if !defined.insert(path.clone()) {
+							return Err(self
+								.cursor
+								.error(format!("table '{}' defined twice", path.join("."))));
+						}
+						self.table(&mut root, &path)?;
+					}
+					self.end_line()?;
+					current = path;
+				},
+				Some(_) => {
+					let table = self.table(&mut root, &current)?;
+					self.parse_key_value(table)?;
+					self.end_line()?;
+				},
+			}
+		}
+	}
+
+	/// The table at a path, creating missing ones.
+	/// Paths through an array of tables go into its last table.
+	fn table<'t>(
+		&self,
+		mut table: &'t mut Table,
+		path: &[String],
+	) -> Result<&'t mut Table, InterpreterErrorKind> {
+		for key in path {
+			table = match table
+				.entry(HashableValue::String(key.clone()))
+				.or_insert_with(|| Value::Map(Table::new()))
+			{
+				Value::Map(table) => table,
+				Value::Array(array) => match array.last_mut() {
+					Some(Value::Map(table)) => table,
+					_ => return Err(self.cursor.error(format!("key '{key}' isn't a table"))),
+				},
+				_ => return Err(self.cursor.error(format!("key '{key}' isn't a table"))),
+			};
+		}
+		Ok(table)
+	}
+
+	fn parse_key_value(&mut self, table: &mut Table) -> Result<(), InterpreterErrorKind> {
+		let key = self.parse_key()?;
+		self.cursor.expect('=')?;
+		self.skip_spaces();
+		let value = self.parse_value()?;
+		let (last, parents) = key.split_last().unwrap();
+		let table = self.table(table, parents)?;
+		let last = HashableValue::String(last.clone());
+		if table.contains_key(&last) {
+			return Err(self
+				.cursor
+				.error(format!("key '{}' defined twice", key.join("."))));
+		}
+		table.insert(last, value);
+		Ok(())
+	}
+
+	/// Parses a key and the spaces after it, giving the parts of a dotted key
+	fn parse_key(&mut self) -> Result<Vec<String>, InterpreterErrorKind> {
+		let mut key = Vec::new();
+		loop {
+			key.push(match self.cursor.peek() {
+				Some('"' | '\'') => self.parse_string(false)?,
+				Some(c) if is_bare_key(c) => {
+					let mut part = String::new();
+					while let Some(c) = self.cursor.peek().filter(|&c| is_bare_key(c)) {
+						part.push(c);
+						self.cursor.next();
+					}
+					part
+				},
+				_ => return Err(self.cursor.error("expected a key")),
+			});
+			self.skip_spaces();
+			if !self.cursor.eat('.') {
+				return Ok(key);
+			}
+			self.skip_spaces();
+		}
+	}
+
+	fn parse_value(&mut self) -> Result<Value, InterpreterErrorKind> {
+		match self.cursor.peek() {
+			Some('"' | '\'') => Ok(Value::String(self.parse_string(true)?)),
+			Some('[') => self.nested(Self::parse_array),
+			Some('{') => self.nested(Self::parse_inline_table),
+			Some(_) => self.parse_scalar(),
+			None => Err(self
+				.cursor
+				.error("expected a value, found the end of the text")),
+		}
+	}
+
+	fn nested(
+		&mut self,
+		parse: fn(&mut Self) -> Result<Value, InterpreterErrorKind>,
+	) -> Result<Value, InterpreterErrorKind> {
+		if self.depth == MAX_DEPTH {
+			return Err(self.cursor.error("arrays and tables nested too deeply"));
+		}
+		self.depth += 1;
+		let value = parse(self)?;
+		self.depth -= 1;
+		Ok(value)
+	}
+
+	fn parse_array(&mut self) -> Result<Value, InterpreterErrorKind> {
+		self.cursor.expect('[')?;
+		let mut array = Vec::new();
+		loop {
+			self.skip_blank();
+			if self.cursor.eat(']') {
+				return Ok(Value::Array(array));
+			}
+			array.push(self.parse_value()?);
+			self.skip_blank();
+			if !self.cursor.eat(',') {
+				self.cursor.expect(']')?;
+				return Ok(Value::Array(array));
+			}
+		}
+	}
+
+	fn parse_inline_table(&mut self) -> Result<Value, InterpreterErrorKind> {
+		self.cursor.expect('{')?;
+		let mut table = Table::new();
+		self.skip_spaces();
+		if self.cursor.eat('}') {
+			return Ok(Value::Map(table));
+		}
+		loop {
+			self.skip_spaces();
+			self.parse_key_value(&mut table)?;
+			self.skip_spaces();
+			if !self.cursor.eat(',') {
+				self.cursor.expect('}')?;
+				return Ok(Value::Map(table));
+			}
+		}
+	}
+
+	/// Parses a basic (`"`) or literal (`'`) string, which may be multi-line (`"""` or `'''`) if allowed
+	fn parse_string(&mut self, allow_multiline: bool) -> Result<String, InterpreterErrorKind> {
+		let quote = self.cursor.next().unwrap();
+		let multiline = allow_multiline
+			&& self.cursor.peek() == Some(quote)
+			&& self.cursor.peek_second() == Some(quote);
+		if multiline {
+			self.cursor.next();
+			self.cursor.next();
+			// A new line right after the opening quotes isn't part of the string
+			self.cursor.eat('\r');
+			self.cursor.eat('\n');
+		}
+
+		let mut string = String::new();
+		loop {
+			match self.cursor.next() {
+				None => return Err(self.cursor.error("unterminated string")),
+				Some(c) if c == quote => {
+					if !multiline {
+						return Ok(string);
+					}
+					// Up to two quotes can come right before the closing ones
+					let mut quotes = 1;
+					while quotes < 5 && self.cursor.eat(quote) {
+						quotes += 1;
+					}
+					if quotes >= 3 {
+						string.extend(iter::repeat_n(quote, quotes - 3));
+						return Ok(string);
+					}
+					string.extend(iter::repeat_n(quote, quotes));
+				},
+				Some('\\') if quote == '"' => {
+					if multiline && matches!(self.cursor.peek(), Some(' ' | '\t' | '\r' | '\n')) {
+						// A backslash at the end of a line trims the whitespace after it
+						while matches!(self.cursor.peek(), Some(' ' | '\t' | '\r' | '\n')) {
+							self.cursor.next();
+						}
+					} else {
+						string.push(self.parse_escape()?);
+					}
+				},
+				Some('\n') if !multiline => {
+					return Err(self.cursor.error("new line in a single-line string"))
+				},
+				Some(c) => string.push(c),
+			}
+		}
+	}
+
+	fn parse_escape(&mut self) -> Result<char, InterpreterErrorKind> {
+		let digits = match self.cursor.next() {
+			Some('b') => return Ok('\u{8}'),
+			Some('t') => return Ok('\t'),
+			Some('n') => return Ok('\n'),
+			Some('f') => return Ok('\u{c}'),
+			Some('r') => return Ok('\r'),
+			Some('e') => return Ok('\u{1b}'),
+			Some('"') => return Ok('"'),
+			Some('\\') => return Ok('\\'),
+			Some('u') => 4,
+			Some('U') => 8,
+			_ => return Err(self.cursor.error("invalid escape sequence")),
+		};
+		let mut code = 0;
+		for _ in 0..digits {
+			let Some(digit) = self.cursor.next().and_then(|c| c.to_digit(16)) else {
+				return Err(self
+					.cursor
+					.error(format!("expected {digits} hex digits in a unicode escape")));
+			};
+			code = code * 16 + digit;
+		}
+		char::from_u32(code).ok_or_else(|| self.cursor.error("invalid unicode escape"))
+	}
+
+	/// Parses a bool, number, date or time
+	fn parse_scalar(&mut self) -> Result<Value, InterpreterErrorKind> {
+		let mut token = String::new();
+		self.take_scalar(&mut token);
+		// The date and time of a date-time can be separated by a space
+		if is_date(&token)
+			&& self.cursor.peek() == Some(' ')
+			&& self
+				.cursor
+				.peek_second()
+				.is_some_and(|c| c.is_ascii_digit())
+		{
+			token.extend(self.cursor.next());
+			self.take_scalar(&mut token);
+		}
+
+		Ok(match token.as_str() {
+			"" => return Err(self.cursor.error("expected a value")),
+			"true" => Value::Bool(true),
+			"false" => Value::Bool(false),
+			"inf" | "+inf" => Value::Float(Float::INFINITY),
+			"-inf" => Value::Float(Float::NEG_INFINITY),
+			"nan" | "+nan" | "-nan" => Value::Float(Float::NAN),
+			_ if is_date(&token) || token.contains(':') => Value::String(token),
+			_ => self.parse_number(&token)?,
+		})
+	}
+
+	fn take_scalar(&mut self, token: &mut String) {
+		while let Some(c) = self
+			.cursor
+			.peek()
+			.filter(|&c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '_' | '.' | ':'))
+		{
+			token.push(c);
+			self.cursor.next();
+		}
+	}
+
+	fn parse_number(&self, token: &str) -> Result<Value, InterpreterErrorKind> {
+		let invalid = || self.cursor.error(format!("invalid value '{token}'"));
+		// Underscores can only separate digits
+		let bytes = token.as_bytes();
+		if bytes.iter().enumerate().any(|(i, &byte)| {
+			byte == b'_'
+				&& !(i > 0
+					&& bytes[i - 1].is_ascii_hexdigit()
+					&& bytes.get(i + 1).is_some_and(u8::is_ascii_hexdigit))
+		}) {
+			return Err(invalid());
+		}
+		let number = token.replace('_', "");
+		let unsigned = number.trim_start_matches(['+', '-']);
+		if unsigned.len() + 1 < number.len() {
+			return Err(invalid());
+		}
+
+		let radix = match unsigned.get(..2) {
+			Some("0x") => 16,
+			Some("0o") => 8,
+			Some("0b") => 2,
+			_ => 10,
+		};
+		if radix != 10 {
+			if unsigned.len() != number.len() {
+				return Err(invalid());
+			}
+			return i64::from_str_radix(&unsigned[2..], radix)
+				.map(integer)
+				.map_err(|_| invalid());
+		}
+
+		let starts_with_digit = unsigned.starts_with(|c: char| c.is_ascii_digit());
+		let ends_with_digit = unsigned.ends_with(|c: char| c.is_ascii_digit());
+		if !starts_with_digit || !ends_with_digit {
+			return Err(invalid());
+		}
+		if unsigned.contains(['.', 'e', 'E']) {
+			return number.parse().map(Value::Float).map_err(|_| invalid());
+		}
+		if unsigned.len() > 1 && unsigned.starts_with('0') {
+			return Err(self.cursor.error("integers can't have leading zeros"));
+		}
+		number.parse().map(integer).map_err(|_| invalid())
+	}
+
+	fn skip_spaces(&mut self) {
+		while matches!(self.cursor.peek(), Some(' ' | '\t')) {
+			self.cursor.next();
+		}
+	}
+
+	/// Skips whitespace, new lines and comments
+	fn skip_blank(&mut self) {
+		loop {
+			match self.cursor.peek() {
+				Some(' ' | '\t' | '\r' | '\n') => {
+					self.cursor.next();
+				},
+				Some('#') => self.skip_comment(),
+				_ => return,
+			}
+		}
+	}
+
+	fn skip_comment(&mut self) {
+		while self.cursor.peek().is_some_and(|c| c != '\n') {
+			self.cursor.next();
+		}
+	}
+
+	/// Skips to the next line, which must only have spaces and a comment before it
+	fn end_line(&mut self) -> Result<(), InterpreterErrorKind> {
+		self.skip_spaces();
+		if self.cursor.peek() == Some('#') {
+			self.skip_comment();
+		}
+		self.cursor.eat('\r');
+		match self.cursor.next() {
+			None | Some('\n') => Ok(()),
+			Some(c) => Err(self
+				.cursor
+				.error(format!("expected a new line, found '{c}'"))),
+		}
+	}
+}
+
+/// Integers outside of the integer range lose precision as floats
+#[allow(clippy::cast_precision_loss)]
+fn integer(integer: i64) -> Value {
+	Integer::try_from(integer).map_or(Value::Float(integer as Float), Value::Integer)
+}
+
+/// Whether a value starts with a date like `1979-05-27`
+fn is_date(token: &str) -> bool {
+	let bytes = token.as_bytes();
+	bytes.len() >= 10
+		&& bytes[4] == b'-'
+		&& bytes[7] == b'-'
+		&& [0, 1, 2, 3, 5, 6, 8, 9]
+			.iter()
+			.all(|&i| bytes[i].is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::interpreter::{
+		error::InterpreterErrorKind,
+		interpret::tests::{evaluate, string},
+		stdlib::cursor::MAX_DEPTH,
+		value::Value,
+	};
+
+	fn toml(call: &str) -> Result<Value, InterpreterErrorKind> {
+		evaluate(&format!(
+			r#"import "std/toml" as toml; let result = {call};"#
+		))
+	}
+
+	#[test]
+	fn round_trip() {
+		let document = "title = 'mill'
+count = 3
+ratio = 0.5
+tags = ['a', 'b']
+inline = { x = 1 }
+dt = 1979-05-27T07:32:00Z
+
+[owner]
+name = 'ada'
+
+[[servers]]
+host = 'alpha'
+
+[[servers]]
+host = 'beta'
+";
+		let expected = r#"count = 3
+dt = "1979-05-27T07:32:00Z"
+ratio = 0.5
+tags = ["a", "b"]
+title = "mill"
+
+[inline]
+x = 1
+
+[owner]
+name = "ada"
+
+[[servers]]
+host = "alpha"
+
+[[servers]]
+host = "beta"
+"#;
+		assert_eq!(
+			toml(&format!(r#"toml.stringify(toml.parse("{document}"))"#)).unwrap(),
+			string(expected)
+		);
+		// Dates have no type of their own
+		assert_eq!(
+			toml(&format!(r#"toml.parse("{document}").dt"#)).unwrap(),
+			string("1979-05-27T07:32:00Z")
+		);
+	}
+
+	#[test]
+	fn errors() {
+		let Err(InterpreterErrorKind::Decode { line, column, .. }) =
+			toml("toml.parse(\"a = 1\nb = \")")
+		else {
+			panic!("expected a decode error");
+		};
+		assert_eq!((line, column), (2, 5));
+		assert!(matches!(
+			toml("toml.parse(\"a = 1\na = 2\")"),
+			Err(InterpreterErrorKind::Decode { line: 2, .. })
+		));
+		assert!(matches!(
+			toml(r#"toml.stringify({"a": null})"#),
+			Err(InterpreterErrorKind::Encode { .. })
+		));
+	}
+
+	#[test]
+	fn nesting_is_limited() {
+		let build = |depth: usize| {
+			format!(
+				r#"{{ let a = []; for i in 0..{} {{ a = [a]; }} toml.stringify({{"a": a}}) }}"#,
+				depth - 1
+			)
+		};
+		assert!(toml(&build(MAX_DEPTH)).is_ok());
+		assert!(matches!(
+			toml(&build(1000)),
+			Err(InterpreterErrorKind::Encode { .. })
+		));
+		let tables = format!(
+			r#"{{ let m = {{}}; for i in 0..={MAX_DEPTH} {{ m = {{"m": m}}; }} toml.stringify(m) }}"#
+		);
+		assert!(matches!(
+			toml(&tables),
+			Err(InterpreterErrorKind::Encode { .. })
+		));
+	}
+}
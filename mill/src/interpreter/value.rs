@@ -1,6 +1,15 @@
-use std::collections::HashMap;
+use std::{
+	cmp::Ordering,
+	collections::HashMap,
+	fmt::{self, Debug, Formatter},
+};
 
-use crate::ast::{Float, Identifier, Integer, Statement};
+use crate::{
+	ast::{Float, Integer, Parameters, Statement},
+	interpreter::{
+		error::InterpreterErrorKind, generator::Generator, module::ModuleId, state::State,
+	},
+};
 
 #[derive(Debug, Clone)]
 pub enum Value {
@@ -10,10 +19,16 @@ pub enum Value {
 	Integer(Integer),
 	Float(Float),
 	String(String),
-	Array(Vec<Value>),
-	Map(HashMap<HashableValue, Value>),
+	Array(Vec<Self>),
+	Map(HashMap<HashableValue, Self>),
+	Range(Range),
 
-	Function(Vec<Identifier>, Vec<Statement>),
+	/// Parameters, body, and the module it was defined in
+	Function(Parameters, Vec<Statement>, ModuleId),
+	NativeFunction(NativeFunction),
+	/// A suspended call of a function containing `yield`.
+	/// Generators are never equal, since their progress can't be compared.
+	Generator(Generator),
 }
 impl Value {
 	pub fn try_as_hashable(self) -> Option<HashableValue> {
@@ -27,12 +42,238 @@ impl Value {
 			_ => return None,
 		})
 	}
+
+	/// Roughly how many bytes the value owns besides itself, for `Config::allocation_limit`
+	pub fn heap_size(&self) -> usize {
+		match self {
+			Self::String(string) => string.len(),
+			Self::Array(array) => array
+				.iter()
+				.map(|value| size_of::<Self>() + value.heap_size())
+				.sum(),
+			Self::Map(map) => map
+				.iter()
+				.map(|(key, value)| key.entry_size() + value.heap_size())
+				.sum(),
+			_ => 0,
+		}
+	}
+
+	/// Total order over all values, usable for sorting.
+	///
+	/// Values of different types are ordered by type first
+	/// (null < bool < number < string < array < range < map < function).
+	/// Floats are ordered with [`Float::total_cmp`], functions compare equal.
+	pub fn total_cmp(&self, other: &Self) -> Ordering {
+		match (self, other) {
+			(Self::Bool(lhs), Self::Bool(rhs)) => lhs.cmp(rhs),
+			(Self::Integer(lhs), Self::Integer(rhs)) => lhs.cmp(rhs),
+			(Self::Integer(lhs), Self::Float(rhs)) => Float::from(*lhs).total_cmp(rhs),
+			(Self::Float(lhs), Self::Integer(rhs)) => lhs.total_cmp(&Float::from(*rhs)),
+			(Self::Float(lhs), Self::Float(rhs)) => lhs.total_cmp(rhs),
+			(Self::String(lhs), Self::String(rhs)) => lhs.cmp(rhs),
+			(Self::Range(lhs), Self::Range(rhs)) => lhs.cmp(rhs),
+			(Self::Array(lhs), Self::Array(rhs)) => {
+				for (lhs, rhs) in lhs.iter().zip(rhs) {
+					match lhs.total_cmp(rhs) {
+						Ordering::Equal => {},
+						o => return o,
+					}
+				}
+				lhs.len().cmp(&rhs.len())
+			},
+			(Self::Map(lhs), Self::Map(rhs)) => {
+				let mut lhs = lhs.iter().collect::<Vec<_>>();
+				let mut rhs = rhs.iter().collect::<Vec<_>>();
+				lhs.sort_by(|a, b| a.0.cmp(b.0));
+				rhs.sort_by(|a, b| a.0.cmp(b.0));
+				for ((lhs_key, lhs_value), (rhs_key, rhs_value)) in lhs.iter().zip(&rhs) {
+					match lhs_key.cmp(rhs_key) {
+						Ordering::Equal => {},
+						o => return o,
+					}
+					match lhs_value.total_cmp(rhs_value) {
+						Ordering::Equal => {},
+						o => return o,
+					}
+				}
+				lhs.len().cmp(&rhs.len())
+			},
+			_ => self.type_rank().cmp(&other.type_rank()),
+		}
+	}
+
+	fn type_rank(&self) -> u8 {
+		match self {
+			Self::Null => 0,
+			Self::Bool(_) => 1,
+			Self::Integer(_) | Self::Float(_) => 2,
+			Self::String(_) => 3,
+			Self::Array(_) => 4,
+			Self::Range(_) => 5,
+			Self::Map(_) => 6,
+			Self::Function(..) | Self::NativeFunction(_) => 7,
+			Self::Generator(_) => 8,
+		}
+	}
+}
+impl PartialEq for Value {
+	fn eq(&self, other: &Self) -> bool {
+		match (self, other) {
+			(Self::Bool(lhs), Self::Bool(rhs)) => lhs == rhs,
+			(Self::Null, Self::Null) => true,
+
+			(Self::Integer(lhs), Self::Integer(rhs)) => lhs == rhs,
+			#[allow(clippy::float_cmp)]
+			(Self::Integer(lhs), Self::Float(rhs)) | (Self::Float(rhs), Self::Integer(lhs)) => {
+				Float::from(*lhs) == *rhs
+			},
+			#[allow(clippy::float_cmp)]
+			(Self::Float(lhs), Self::Float(rhs)) => lhs == rhs,
+			(Self::String(lhs), Self::String(rhs)) => lhs == rhs,
+			(Self::Array(lhs), Self::Array(rhs)) => lhs == rhs,
+			(Self::Map(lhs), Self::Map(rhs)) => lhs == rhs,
+			(Self::Range(lhs), Self::Range(rhs)) => lhs == rhs,
+
+			(
+				Self::Function(lhs_parameters, lhs_body, lhs_module),
+				Self::Function(rhs_parameters, rhs_body, rhs_module),
+			) => {
+				lhs_parameters == rhs_parameters && lhs_body == rhs_body && lhs_module == rhs_module
+			},
+			(Self::NativeFunction(lhs), Self::NativeFunction(rhs)) => lhs.name == rhs.name,
+
+			_ => false,
+		}
+	}
 }
-#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+impl PartialOrd for Value {
+	/// Numbers compare numerically, strings, arrays and ranges lexicographically.
+	/// Every other combination is unordered.
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		match (self, other) {
+			(Self::Integer(lhs), Self::Integer(rhs)) => lhs.partial_cmp(rhs),
+			(Self::Integer(lhs), Self::Float(rhs)) => Float::from(*lhs).partial_cmp(rhs),
+			(Self::Float(lhs), Self::Integer(rhs)) => lhs.partial_cmp(&Float::from(*rhs)),
+			(Self::Float(lhs), Self::Float(rhs)) => lhs.partial_cmp(rhs),
+			(Self::String(lhs), Self::String(rhs)) => lhs.partial_cmp(rhs),
+			(Self::Range(lhs), Self::Range(rhs)) => lhs.partial_cmp(rhs),
+			(Self::Array(lhs), Self::Array(rhs)) => {
+				for (lhs, rhs) in lhs.iter().zip(rhs) {
+					match lhs.partial_cmp(rhs)? {
+						Ordering::Equal => {},
+						o => return Some(o),
+					}
+				}
+				lhs.len().partial_cmp(&rhs.len())
+			},
+			_ => None,
+		}
+	}
+}
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
 pub enum HashableValue {
-	Bool(bool),
 	Null,
+	Bool(bool),
 
 	Integer(Integer),
 	String(String),
 }
+impl HashableValue {
+	/// Roughly how many bytes a map entry with the key takes, besides the value's own
+	pub fn entry_size(&self) -> usize {
+		let key = match self {
+			Self::String(string) => string.len(),
+			_ => 0,
+		};
+		size_of::<(Self, Value)>() + key
+	}
+}
+impl From<HashableValue> for Value {
+	fn from(value: HashableValue) -> Self {
+		match value {
+			HashableValue::Null => Self::Null,
+			HashableValue::Bool(b) => Self::Bool(b),
+
+			HashableValue::Integer(i) => Self::Integer(i),
+			HashableValue::String(s) => Self::String(s),
+		}
+	}
+}
+
+/// A lazy sequence of integers.
+/// Ranges are equal and ordered by their elements, so all empty ranges are equal.
+#[derive(Debug, Clone, Copy)]
+pub struct Range {
+	pub start: Integer,
+	pub end: Integer,
+	pub step: Integer,
+	pub inclusive: bool,
+}
+impl Range {
+	pub fn len(&self) -> usize {
+		let (start, end, step) = (
+			i64::from(self.start),
+			i64::from(self.end),
+			i64::from(self.step),
+		);
+		let distance = if step > 0 { end - start } else { start - end };
+		let step = step.abs();
+		let len = if self.inclusive {
+			if distance < 0 {
+				0
+			} else {
+				distance / step + 1
+			}
+		} else if distance <= 0 {
+			0
+		} else {
+			(distance + step - 1) / step
+		};
+		usize::try_from(len).unwrap()
+	}
+
+	pub fn get(&self, index: usize) -> Option<Integer> {
+		if index >= self.len() {
+			return None;
+		}
+		let index = i64::try_from(index).ok()?;
+		Integer::try_from(i64::from(self.start) + index * i64::from(self.step)).ok()
+	}
+
+	pub fn contains(&self, value: Integer) -> bool {
+		let offset = i64::from(value) - i64::from(self.start);
+		let step = i64::from(self.step);
+		offset % step == 0 && usize::try_from(offset / step).is_ok_and(|index| index < self.len())
+	}
+}
+impl PartialEq for Range {
+	fn eq(&self, other: &Self) -> bool { self.cmp(other) == Ordering::Equal }
+}
+impl Eq for Range {}
+impl PartialOrd for Range {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+impl Ord for Range {
+	/// Compares the elements in order, like arrays. Ranges whose first two elements are
+	/// the same have the same step, so the rest of their common elements are the same too.
+	fn cmp(&self, other: &Self) -> Ordering {
+		for index in 0..2 {
+			match (self.get(index), other.get(index)) {
+				(Some(lhs), Some(rhs)) if lhs != rhs => return lhs.cmp(&rhs),
+				(Some(_), Some(_)) => {},
+				_ => break,
+			}
+		}
+		self.len().cmp(&other.len())
+	}
+}
+
+#[derive(Clone, Copy)]
+pub struct NativeFunction {
+	pub name: &'static str,
+	pub function: fn(&mut State, Vec<Value>) -> Result<Value, InterpreterErrorKind>,
+}
+impl Debug for NativeFunction {
+	fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> { f.write_str(self.name) }
+}
@@ -11,3 +11,4 @@
 pub mod ast;
 pub mod interpreter;
 pub mod parser;
+mod stack;
@@ -3,19 +3,29 @@ use std::{
 	fmt::{self, Display, Formatter},
 };
 
-use crate::parser::{lexer::Token, LineNumber};
+use crate::{
+	ast::Identifier,
+	parser::{lexer::Token, LineNumber},
+};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ParseError {
 	pub source_file: Option<String>,
 	pub line_number: LineNumber,
 	pub kind: ParseErrorKind,
 }
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ParseErrorKind {
 	UnexpectedCharacter(char),
 	NonAsciiByte(u8),
 	ExpressionNotAssignable,
+	InvalidKeywordArgument,
+	PositionalArgumentAfterKeyword,
+	UnknownLabel(Identifier),
+	/// `break` with a value out of a `for` or `while`, or outside of any loop
+	BreakValueOutsideLoop,
+	/// `yield` in a block used as a value, which can't be suspended halfway
+	YieldInExpression,
 	UnexpectedToken {
 		expected: &'static str,
 		found: Token,
@@ -61,6 +71,26 @@ impl Display for ParseError {
 			ParseErrorKind::ExpressionNotAssignable => {
 				writeln!(f, "Expression not assignable")?;
 			},
+			ParseErrorKind::InvalidKeywordArgument => {
+				writeln!(f, "Keyword argument name must be an identifier")?;
+			},
+			ParseErrorKind::PositionalArgumentAfterKeyword => {
+				writeln!(f, "Positional argument after keyword argument")?;
+			},
+			ParseErrorKind::UnknownLabel(ref label) => {
+				writeln!(f, "Unknown label '{}", label.0)?;
+			},
+			ParseErrorKind::BreakValueOutsideLoop => {
+				writeln!(f, "'break' with a value can only leave a 'loop'")?;
+				writeln!(f, "    note: 'for' and 'while' loops have no value")?;
+			},
+			ParseErrorKind::YieldInExpression => {
+				writeln!(f, "'yield' can't be used in a block that has a value")?;
+				writeln!(
+					f,
+					"    note: use it in an 'if', loop or block statement instead"
+				)?;
+			},
 		}
 
 		Ok(())
@@ -68,3 +98,37 @@ impl Display for ParseError {
 }
 
 impl Error for ParseError {}
+
+#[derive(Debug, Clone)]
+pub struct ParseWarning {
+	pub source_file: Option<String>,
+	pub line_number: LineNumber,
+	pub kind: ParseWarningKind,
+}
+#[derive(Debug, Clone)]
+pub enum ParseWarningKind {
+	UnreachableMatchArm,
+}
+
+impl Display for ParseWarning {
+	fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+		write!(
+			f,
+			"{}:{} - warning: ",
+			self.source_file.as_deref().unwrap_or("<source>"),
+			self.line_number
+		)?;
+
+		match self.kind {
+			ParseWarningKind::UnreachableMatchArm => {
+				writeln!(f, "Unreachable match arm")?;
+				writeln!(
+					f,
+					"    note: A previous arm without a guard matches any value"
+				)?;
+			},
+		}
+
+		Ok(())
+	}
+}
@@ -1,17 +1,38 @@
 use crate::{
-	ast::{BinaryOperation, Expression, UnaryOperation},
+	ast::{
+		Arguments, ArrayElement, BinaryOperation, Expression, MapEntry, MatchArm, Parameter,
+		Parameters, Statement, UnaryOperation,
+	},
 	parser::{
-		error::{ParseError, ParseErrorKind},
+		error::{ParseError, ParseErrorKind, ParseWarningKind},
 		lexer::{Lexer, Symbol, Token},
-		statement::parse_block,
+		pattern::parse_pattern,
+		statement::{
+			parse_block, parse_block_rest, parse_expression_statement, parse_if_branches,
+			parse_loop_body,
+		},
 	},
+	stack,
 };
 
+/// Checks that a block used as a value has no `yield`, since generators can only be
+/// suspended between statements
+fn without_yield(body: Vec<Statement>, lexer: &Lexer) -> Result<Vec<Statement>, ParseError> {
+	if body.iter().any(Statement::contains_yield) {
+		return Err(lexer.error(ParseErrorKind::YieldInExpression));
+	}
+	Ok(body)
+}
+
 pub fn parse_expression(lexer: &mut Lexer) -> Result<Expression, ParseError> {
 	parse_expression_bp(lexer, 0)
 }
 
 fn parse_expression_bp(lexer: &mut Lexer, min_bp: u8) -> Result<Expression, ParseError> {
+	stack::grow(|| parse_operations(lexer, min_bp))
+}
+
+fn parse_operations(lexer: &mut Lexer, min_bp: u8) -> Result<Expression, ParseError> {
 	let mut lhs = match lexer.next()? {
 		Token::True => Expression::True,
 		Token::False => Expression::False,
@@ -41,10 +62,7 @@ fn parse_expression_bp(lexer: &mut Lexer, min_bp: u8) -> Result<Expression, Pars
 			loop {
 				match lexer.peek()? {
 					Token::Eof | Token::Symbol(Symbol::SquareRight) => break,
-					_ => {
-						let value = parse_expression(lexer)?;
-						initializers.push(value);
-					},
+					_ => initializers.push(parse_array_element(lexer)?),
 				}
 				match lexer.peek()? {
 					Token::Symbol(Symbol::Comma) => {
@@ -66,53 +84,35 @@ fn parse_expression_bp(lexer: &mut Lexer, min_bp: u8) -> Result<Expression, Pars
 
 			Expression::Array(initializers)
 		},
-		Token::Symbol(Symbol::CurlyLeft) => {
-			let mut initializers = Vec::new();
-
-			loop {
-				let key = match lexer.peek()? {
-					Token::Eof => {
-						lexer.next()?;
-						return Err(lexer.error(ParseErrorKind::UnexpectedToken {
-							expected: "closing curly bracket",
-							found: Token::Eof,
-						}));
-					},
-					Token::Symbol(Symbol::CurlyRight) => {
-						lexer.next()?;
-						break;
-					},
-					_ => parse_expression(lexer)?,
-				};
-				match lexer.next()? {
-					Token::Symbol(Symbol::Colon) => {},
-					t => {
-						return Err(lexer.error(ParseErrorKind::UnexpectedToken {
-							expected: "colon",
-							found: t,
-						}))
-					},
-				}
-				let value = parse_expression(lexer)?;
-				initializers.push((key, value));
-				match lexer.peek()? {
-					Token::Symbol(Symbol::Comma) => {
-						lexer.next()?;
-					},
-					Token::Symbol(Symbol::CurlyRight) => {
-						lexer.next()?;
-						break;
-					},
-					t => {
-						return Err(lexer.error(ParseErrorKind::UnexpectedToken {
-							expected: "comma or closing curly bracket",
-							found: t,
-						}))
-					},
+		Token::Symbol(Symbol::CurlyLeft) => match lexer.peek()? {
+			Token::Symbol(Symbol::CurlyRight | Symbol::Ellipsis) => parse_map(lexer, None)?,
+			t if starts_statement(&t) => {
+				Expression::Block(without_yield(parse_block_rest(lexer, Vec::new())?, lexer)?)
+			},
+			// Either the first key of a map or the first statement of a block
+			_ => {
+				let line_number = lexer.line_number();
+				let first = parse_expression(lexer)?;
+				if lexer.peek()? == Token::Symbol(Symbol::Colon) {
+					parse_map(lexer, Some(first))?
+				} else {
+					let statement = parse_expression_statement(lexer, first, line_number)?;
+					Expression::Block(without_yield(
+						parse_block_rest(lexer, vec![statement])?,
+						lexer,
+					)?)
 				}
+			},
+		},
+		Token::If => {
+			let branches = parse_if_branches(lexer)?;
+			if branches
+				.iter()
+				.any(|(_, body)| body.iter().any(Statement::contains_yield))
+			{
+				return Err(lexer.error(ParseErrorKind::YieldInExpression));
 			}
-
-			Expression::Map(initializers)
+			Expression::If { branches }
 		},
 		Token::Symbol(op) if op.prefix_bp().is_some() => {
 			let ((), r_bp) = op.prefix_bp().unwrap();
@@ -120,6 +120,7 @@ fn parse_expression_bp(lexer: &mut Lexer, min_bp: u8) -> Result<Expression, Pars
 			let operator = match op {
 				Symbol::No => UnaryOperation::Not,
 				Symbol::Sub => UnaryOperation::Neg,
+				Symbol::BitNot => UnaryOperation::BitNot,
 				_ => unimplemented!(),
 			};
 			Expression::UnaryOperation(Box::new(rhs), operator)
@@ -135,19 +136,37 @@ fn parse_expression_bp(lexer: &mut Lexer, min_bp: u8) -> Result<Expression, Pars
 				},
 			}
 
-			let mut parameters = Vec::new();
+			let mut parameters = Parameters {
+				positional: Vec::new(),
+				rest: None,
+			};
 
 			loop {
-				if lexer.peek()? == Token::Symbol(Symbol::ParenRight) {
-					break;
-				}
-				match lexer.next()? {
-					Token::Identifier(i) => parameters.push(i),
-					t => {
-						return Err(lexer.error(ParseErrorKind::UnexpectedToken {
-							expected: "identifier",
-							found: t,
-						}))
+				match lexer.peek()? {
+					Token::Symbol(Symbol::ParenRight) => break,
+					Token::Symbol(Symbol::Ellipsis) => {
+						lexer.next()?;
+						match lexer.next()? {
+							Token::Identifier(i) => parameters.rest = Some(i),
+							t => {
+								return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+									expected: "identifier",
+									found: t,
+								}))
+							},
+						}
+						// The rest parameter must be last
+						break;
+					},
+					_ => {
+						let pattern = parse_pattern(lexer)?;
+						let default = if lexer.peek()? == Token::Symbol(Symbol::Eq) {
+							lexer.next()?;
+							Some(parse_expression(lexer)?)
+						} else {
+							None
+						};
+						parameters.positional.push(Parameter { pattern, default });
 					},
 				}
 				if lexer.peek()? != Token::Symbol(Symbol::Comma) {
@@ -165,10 +184,42 @@ fn parse_expression_bp(lexer: &mut Lexer, min_bp: u8) -> Result<Expression, Pars
 				},
 			}
 
-			let statements = parse_block(lexer)?;
+			// Loops around the function can't be left from inside it
+			let loops = lexer.enter_function();
+			let statements = parse_block(lexer);
+			lexer.exit_function(loops);
 
-			Expression::Function(parameters, statements)
+			Expression::Function(parameters, statements?)
 		},
+		Token::Loop => Expression::Loop {
+			label: None,
+			body: without_yield(parse_loop_body(lexer, None, true)?, lexer)?,
+		},
+		Token::Label(label) => {
+			match lexer.next()? {
+				Token::Symbol(Symbol::Colon) => {},
+				t => {
+					return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+						expected: "colon after label",
+						found: t,
+					}))
+				},
+			}
+			match lexer.next()? {
+				Token::Loop => {},
+				t => {
+					return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+						expected: "keyword 'loop'",
+						found: t,
+					}))
+				},
+			}
+			Expression::Loop {
+				body: without_yield(parse_loop_body(lexer, Some(label.clone()), true)?, lexer)?,
+				label: Some(label),
+			}
+		},
+		Token::Match => parse_match(lexer)?,
 		t => {
 			return Err(lexer.error(ParseErrorKind::UnexpectedToken {
 				expected: "start of expression",
@@ -177,10 +228,15 @@ fn parse_expression_bp(lexer: &mut Lexer, min_bp: u8) -> Result<Expression, Pars
 		},
 	};
 
+	// Whether `lhs` is a chain of member accesses, indexes and calls containing a `?.`
+	let mut optional_chain = false;
+
 	loop {
 		let op = match lexer.peek()? {
-			Token::Eof => break,
+			// `by` ends the bound of a range, the step is parsed with the range
+			Token::Eof | Token::By => break,
 			Token::Symbol(op) => op,
+			Token::In => Symbol::In,
 			t => {
 				return Err(lexer.error(ParseErrorKind::UnexpectedToken {
 					expected: "operator or end of expression",
@@ -189,6 +245,15 @@ fn parse_expression_bp(lexer: &mut Lexer, min_bp: u8) -> Result<Expression, Pars
 			},
 		};
 
+		if optional_chain
+			&& !matches!(
+				op,
+				Symbol::Dot | Symbol::QuestionDot | Symbol::SquareLeft | Symbol::ParenLeft
+			) {
+			lhs = Expression::OptionalChain(Box::new(lhs));
+			optional_chain = false;
+		}
+
 		if let Some((l_bp, ())) = op.postfix_bp() {
 			if l_bp < min_bp {
 				break;
@@ -196,8 +261,39 @@ fn parse_expression_bp(lexer: &mut Lexer, min_bp: u8) -> Result<Expression, Pars
 			lexer.next()?;
 
 			lhs = match op {
+				// Followed by a member, or an index or call parsed as usual
+				Symbol::QuestionDot => {
+					optional_chain = true;
+					let lhs = Expression::NullSafe(Box::new(lhs));
+					match lexer.peek()? {
+						Token::Identifier(member) => {
+							lexer.next()?;
+							Expression::Member(Box::new(lhs), member)
+						},
+						Token::Symbol(Symbol::SquareLeft | Symbol::ParenLeft) => lhs,
+						t => {
+							return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+								expected: "identifier, index or arguments",
+								found: t,
+							}))
+						},
+					}
+				},
 				Symbol::SquareLeft => {
-					let rhs = parse_expression(lexer)?;
+					let start = match lexer.peek()? {
+						Token::Symbol(Symbol::Colon) => None,
+						_ => Some(parse_expression(lexer)?),
+					};
+					let slice = lexer.peek()? == Token::Symbol(Symbol::Colon);
+					let end = if slice {
+						lexer.next()?;
+						match lexer.peek()? {
+							Token::Symbol(Symbol::SquareRight) => None,
+							_ => Some(parse_expression(lexer)?),
+						}
+					} else {
+						None
+					};
 					match lexer.next()? {
 						Token::Symbol(Symbol::SquareRight) => {},
 						t => {
@@ -207,16 +303,38 @@ fn parse_expression_bp(lexer: &mut Lexer, min_bp: u8) -> Result<Expression, Pars
 							}))
 						},
 					}
-					Expression::BinaryOperation(Box::new([lhs, rhs]), BinaryOperation::Index)
+					if slice {
+						Expression::Slice(Box::new(lhs), start.map(Box::new), end.map(Box::new))
+					} else {
+						Expression::BinaryOperation(
+							Box::new([lhs, start.unwrap()]),
+							BinaryOperation::Index,
+						)
+					}
 				},
 				Symbol::ParenLeft => {
-					let mut arguments = Vec::new();
+					let mut arguments = Arguments {
+						positional: Vec::new(),
+						keyword: Vec::new(),
+					};
 
 					loop {
 						if lexer.peek()? == Token::Symbol(Symbol::ParenRight) {
 							break;
 						}
-						arguments.push(parse_expression(lexer)?);
+						let argument = parse_array_element(lexer)?;
+						if lexer.peek()? == Token::Symbol(Symbol::Colon) {
+							let ArrayElement::Single(Expression::Identifier(name)) = argument
+							else {
+								return Err(lexer.error(ParseErrorKind::InvalidKeywordArgument));
+							};
+							lexer.next()?;
+							arguments.keyword.push((name, parse_expression(lexer)?));
+						} else if arguments.keyword.is_empty() {
+							arguments.positional.push(argument);
+						} else {
+							return Err(lexer.error(ParseErrorKind::PositionalArgumentAfterKeyword));
+						}
 						if lexer.peek()? != Token::Symbol(Symbol::Comma) {
 							break;
 						}
@@ -250,7 +368,20 @@ fn parse_expression_bp(lexer: &mut Lexer, min_bp: u8) -> Result<Expression, Pars
 				use BinaryOperation as B;
 				use Symbol as S;
 
-				if op == S::Dot {
+				if op == S::DotDot || op == S::DotDotEq {
+					let end = parse_expression_bp(lexer, r_bp)?;
+					let step = if lexer.peek()? == Token::By {
+						lexer.next()?;
+						Some(Box::new(parse_expression_bp(lexer, r_bp)?))
+					} else {
+						None
+					};
+					Expression::Range {
+						bounds: Box::new([lhs, end]),
+						step,
+						inclusive: op == S::DotDotEq,
+					}
+				} else if op == S::Dot {
 					let member = match lexer.next()? {
 						Token::Identifier(i) => i,
 						t => {
@@ -270,6 +401,8 @@ fn parse_expression_bp(lexer: &mut Lexer, min_bp: u8) -> Result<Expression, Pars
 						S::Mul => B::Mul,
 						S::Div => B::Div,
 						S::Mod => B::Mod,
+						S::Pow => B::Pow,
+						S::FloorDiv => B::FloorDiv,
 
 						S::EqEq => B::Eq,
 						S::NoEq => B::NoEq,
@@ -280,6 +413,15 @@ fn parse_expression_bp(lexer: &mut Lexer, min_bp: u8) -> Result<Expression, Pars
 
 						S::And => B::And,
 						S::Or => B::Or,
+						S::QuestionQuestion => B::Coalesce,
+
+						S::BitAnd => B::BitAnd,
+						S::BitOr => B::BitOr,
+						S::BitXor => B::BitXor,
+						S::Shl => B::Shl,
+						S::Shr => B::Shr,
+
+						S::In => B::In,
 
 						_ => unimplemented!(),
 					};
@@ -292,5 +434,169 @@ fn parse_expression_bp(lexer: &mut Lexer, min_bp: u8) -> Result<Expression, Pars
 		break;
 	}
 
+	if optional_chain {
+		lhs = Expression::OptionalChain(Box::new(lhs));
+	}
 	Ok(lhs)
 }
+/// Parses the entries of a map literal after the opening curly bracket,
+/// the key of the first entry may have been parsed already
+fn parse_map(
+	lexer: &mut Lexer,
+	mut first_key: Option<Expression>,
+) -> Result<Expression, ParseError> {
+	let mut initializers = Vec::new();
+
+	loop {
+		let key = match first_key.take() {
+			Some(key) => Some(key),
+			None => match lexer.peek()? {
+				Token::Eof => {
+					lexer.next()?;
+					return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+						expected: "closing curly bracket",
+						found: Token::Eof,
+					}));
+				},
+				Token::Symbol(Symbol::CurlyRight) => {
+					lexer.next()?;
+					break;
+				},
+				Token::Symbol(Symbol::Ellipsis) => None,
+				_ => Some(parse_expression(lexer)?),
+			},
+		};
+		if let Some(key) = key {
+			match lexer.next()? {
+				Token::Symbol(Symbol::Colon) => {},
+				t => {
+					return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+						expected: "colon",
+						found: t,
+					}))
+				},
+			}
+			let value = parse_expression(lexer)?;
+			initializers.push(MapEntry::Pair(key, value));
+		} else {
+			lexer.next()?;
+			initializers.push(MapEntry::Spread(parse_expression(lexer)?));
+		}
+		match lexer.peek()? {
+			Token::Symbol(Symbol::Comma) => {
+				lexer.next()?;
+			},
+			Token::Symbol(Symbol::CurlyRight) => {
+				lexer.next()?;
+				break;
+			},
+			t => {
+				return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+					expected: "comma or closing curly bracket",
+					found: t,
+				}))
+			},
+		}
+	}
+
+	Ok(Expression::Map(initializers))
+}
+/// Parses a `match` after the keyword
+pub fn parse_match(lexer: &mut Lexer) -> Result<Expression, ParseError> {
+	let scrutinee = parse_expression(lexer)?;
+
+	match lexer.next()? {
+		Token::Symbol(Symbol::CurlyLeft) => {},
+		t => {
+			return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+				expected: "match arms",
+				found: t,
+			}))
+		},
+	}
+
+	let mut arms = Vec::new();
+	let mut unreachable = false;
+
+	loop {
+		if lexer.peek()? == Token::Symbol(Symbol::CurlyRight) {
+			lexer.next()?;
+			break;
+		}
+		let line_number = lexer.line_number();
+
+		let pattern = parse_pattern(lexer)?;
+		let guard = if lexer.peek()? == Token::If {
+			lexer.next()?;
+			Some(parse_expression(lexer)?)
+		} else {
+			None
+		};
+		match lexer.next()? {
+			Token::Symbol(Symbol::FatArrow) => {},
+			t => {
+				return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+					expected: "'=>'",
+					found: t,
+				}))
+			},
+		}
+		let body = parse_expression(lexer)?;
+
+		if unreachable {
+			lexer.warn(line_number, ParseWarningKind::UnreachableMatchArm);
+		}
+		unreachable |= guard.is_none() && pattern.is_irrefutable();
+
+		arms.push(MatchArm {
+			pattern,
+			guard,
+			body,
+		});
+
+		match lexer.next()? {
+			Token::Symbol(Symbol::Comma) => {},
+			Token::Symbol(Symbol::CurlyRight) => break,
+			t => {
+				return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+					expected: "comma or closing curly bracket",
+					found: t,
+				}))
+			},
+		}
+	}
+
+	Ok(Expression::Match(Box::new(scrutinee), arms))
+}
+/// Whether a block in an expression starts with the token, rather than a map
+fn starts_statement(token: &Token) -> bool {
+	matches!(
+		token,
+		Token::Let
+			| Token::Const
+			| Token::If
+			| Token::Match
+			| Token::For
+			| Token::While
+			| Token::Loop
+			| Token::Label(_)
+			| Token::Return
+			| Token::Break
+			| Token::Continue
+			| Token::Throw
+			| Token::Try
+			| Token::Yield
+			| Token::Import
+			| Token::From
+			| Token::Symbol(Symbol::CurlyLeft)
+	)
+}
+/// Parses an element of an array literal or an argument, which may be spread
+fn parse_array_element(lexer: &mut Lexer) -> Result<ArrayElement, ParseError> {
+	Ok(if lexer.peek()? == Token::Symbol(Symbol::Ellipsis) {
+		lexer.next()?;
+		ArrayElement::Spread(parse_expression(lexer)?)
+	} else {
+		ArrayElement::Single(parse_expression(lexer)?)
+	})
+}
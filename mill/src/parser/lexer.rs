@@ -7,7 +7,7 @@ use std::{
 use crate::{
 	ast::{Float, Identifier, Integer},
 	parser::{
-		error::{ParseError, ParseErrorKind},
+		error::{ParseError, ParseErrorKind, ParseWarning, ParseWarningKind},
 		LineNumber,
 	},
 };
@@ -18,6 +18,8 @@ pub enum Token {
 	Integer(Integer),
 	Float(Float),
 	String(String),
+	/// `'name`, naming a loop
+	Label(Identifier),
 
 	Symbol(Symbol),
 
@@ -27,6 +29,7 @@ pub enum Token {
 	Fn,
 	Return,
 	Let,
+	Const,
 	If,
 	Else,
 	Loop,
@@ -35,25 +38,50 @@ pub enum Token {
 	For,
 	In,
 	While,
+	By,
+	Try,
+	Catch,
+	Finally,
+	Throw,
+	Match,
+	Import,
+	From,
+	As,
+	Export,
+	Yield,
 
 	Eof,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Lexer<'a> {
 	source: &'a [u8],
 	current_index: usize,
 	current_token: Option<Token>,
 	line_number: LineNumber,
+	source_file: Option<String>,
+	warnings: Vec<ParseWarning>,
+	/// Loops around the code being parsed, innermost last
+	loops: Vec<LoopContext>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LoopContext {
+	pub label: Option<Identifier>,
+	/// Only `loop` can be left with `break value`, `for` and `while` have no value
+	pub has_value: bool,
 }
 
 impl<'a> Lexer<'a> {
-	pub fn new(input: &'a str) -> Self {
+	pub fn new(input: &'a str, source_file: Option<String>) -> Self {
 		Lexer {
 			source: input.as_bytes(),
 			current_index: 0,
 			current_token: None,
 			line_number: 1,
+			source_file,
+			warnings: Vec::new(),
+			loops: Vec::new(),
 		}
 	}
 
@@ -71,14 +99,51 @@ impl<'a> Lexer<'a> {
 		Ok(self.current_token.clone().unwrap())
 	}
 
+	pub fn line_number(&self) -> LineNumber { self.line_number }
+
 	pub fn error(&self, kind: ParseErrorKind) -> ParseError {
 		ParseError {
-			source_file: None,
+			source_file: self.source_file.clone(),
 			line_number: self.line_number,
 			kind,
 		}
 	}
 
+	pub fn warn(&mut self, line_number: LineNumber, kind: ParseWarningKind) {
+		self.warnings.push(ParseWarning {
+			source_file: self.source_file.clone(),
+			line_number,
+			kind,
+		});
+	}
+
+	pub fn enter_loop(&mut self, label: Option<Identifier>, has_value: bool) {
+		self.loops.push(LoopContext { label, has_value });
+	}
+
+	pub fn exit_loop(&mut self) { self.loops.pop(); }
+
+	/// Loops outside of a function can't be targeted from inside it.
+	/// Returns the loops to restore with `exit_function`.
+	pub fn enter_function(&mut self) -> Vec<LoopContext> { std::mem::take(&mut self.loops) }
+
+	pub fn exit_function(&mut self, loops: Vec<LoopContext>) { self.loops = loops; }
+
+	/// Finds the loop a `break` or `continue` refers to
+	pub fn target_loop(&self, label: Option<&Identifier>) -> Option<&LoopContext> {
+		label.map_or_else(
+			|| self.loops.last(),
+			|label| {
+				self.loops
+					.iter()
+					.rev()
+					.find(|context| context.label.as_ref() == Some(label))
+			},
+		)
+	}
+
+	pub fn take_warnings(&mut self) -> Vec<ParseWarning> { std::mem::take(&mut self.warnings) }
+
 	fn parse_token(&mut self) -> Result<Token, ParseError> {
 		let mut token_start = self.current_index;
 		let mut token_end = 1;
@@ -180,6 +245,7 @@ impl<'a> Lexer<'a> {
 						"fn" => Token::Fn,
 						"return" => Token::Return,
 						"let" => Token::Let,
+						"const" => Token::Const,
 						"if" => Token::If,
 						"else" => Token::Else,
 						"loop" => Token::Loop,
@@ -188,9 +254,40 @@ impl<'a> Lexer<'a> {
 						"for" => Token::For,
 						"in" => Token::In,
 						"while" => Token::While,
+						"by" => Token::By,
+						"try" => Token::Try,
+						"catch" => Token::Catch,
+						"finally" => Token::Finally,
+						"throw" => Token::Throw,
+						"match" => Token::Match,
+						"import" => Token::Import,
+						"from" => Token::From,
+						"as" => Token::As,
+						"export" => Token::Export,
+						"yield" => Token::Yield,
 						_ => Token::Identifier(Identifier(identifier)),
 					}
 				},
+				'\'' => {
+					if !self
+						.source
+						.get(token_start + 1)
+						.is_some_and(|c| c.is_ascii_alphabetic() || *c == b'_')
+					{
+						return Err(self.error(ParseErrorKind::UnexpectedCharacter('\'')));
+					}
+					while self
+						.source
+						.get(token_start + token_end)
+						.is_some_and(|c| c.is_ascii_alphanumeric() || *c == b'_')
+					{
+						token_end += 1;
+					}
+					let label =
+						str::from_utf8(&self.source[(token_start + 1)..(token_start + token_end)])
+							.unwrap();
+					Token::Label(Identifier(label.to_owned()))
+				},
 				'"' => {
 					while self
 						.source
@@ -206,6 +303,33 @@ impl<'a> Lexer<'a> {
 					Token::String(string.to_owned())
 				},
 
+				'.' if self.source.get(token_start + 1).is_some_and(|c| *c == b'.') => {
+					token_end += 1;
+					match self.source.get(token_start + 2) {
+						Some(b'=') => {
+							token_end += 1;
+							Token::Symbol(Symbol::DotDotEq)
+						},
+						Some(b'.') => {
+							token_end += 1;
+							Token::Symbol(Symbol::Ellipsis)
+						},
+						_ => Token::Symbol(Symbol::DotDot),
+					}
+				},
+
+				'?' => match self.source.get(token_start + 1) {
+					Some(b'.') => {
+						token_end += 1;
+						Token::Symbol(Symbol::QuestionDot)
+					},
+					Some(b'?') => {
+						token_end += 1;
+						Token::Symbol(Symbol::QuestionQuestion)
+					},
+					_ => return Err(self.error(ParseErrorKind::UnexpectedCharacter('?'))),
+				},
+
 				':' => Token::Symbol(Symbol::Colon),
 				';' => Token::Symbol(Symbol::Semicolon),
 				',' => Token::Symbol(Symbol::Comma),
@@ -218,12 +342,42 @@ impl<'a> Lexer<'a> {
 				'[' => Token::Symbol(Symbol::SquareLeft),
 				']' => Token::Symbol(Symbol::SquareRight),
 
+				'+' | '-' | '*' | '/' | '%' | '&' | '|' | '^'
+					if self.source.get(token_start + 1).is_some_and(|c| *c == b'=') =>
+				{
+					token_end += 1;
+					match self.source[token_start] as char {
+						'+' => Token::Symbol(Symbol::AddEq),
+						'-' => Token::Symbol(Symbol::SubEq),
+						'*' => Token::Symbol(Symbol::MulEq),
+						'/' => Token::Symbol(Symbol::DivEq),
+						'%' => Token::Symbol(Symbol::ModEq),
+						'&' => Token::Symbol(Symbol::BitAndEq),
+						'|' => Token::Symbol(Symbol::BitOrEq),
+						'^' => Token::Symbol(Symbol::BitXorEq),
+						_ => unreachable!(),
+					}
+				},
+
+				'*' if self.source.get(token_start + 1).is_some_and(|c| *c == b'*') => {
+					token_end += 1;
+					Token::Symbol(Symbol::Pow)
+				},
+				'/' if self.source.get(token_start + 1).is_some_and(|c| *c == b'/') => {
+					token_end += 1;
+					Token::Symbol(Symbol::FloorDiv)
+				},
+
 				'+' => Token::Symbol(Symbol::Add),
 				'-' => Token::Symbol(Symbol::Sub),
 				'*' => Token::Symbol(Symbol::Mul),
 				'/' => Token::Symbol(Symbol::Div),
 				'%' => Token::Symbol(Symbol::Mod),
 
+				'=' if self.source.get(token_start + 1).is_some_and(|c| *c == b'>') => {
+					token_end += 1;
+					Token::Symbol(Symbol::FatArrow)
+				},
 				'=' | '!' | '<' | '>'
 					if self.source.get(token_start + 1).is_some_and(|c| *c == b'=') =>
 				{
@@ -237,6 +391,26 @@ impl<'a> Lexer<'a> {
 					}
 				},
 
+				'<' | '>'
+					if self.source.get(token_start + 1) == Some(&self.source[token_start])
+						&& self.source.get(token_start + 2).is_some_and(|c| *c == b'=') =>
+				{
+					token_end += 2;
+					match self.source[token_start] as char {
+						'<' => Token::Symbol(Symbol::ShlEq),
+						'>' => Token::Symbol(Symbol::ShrEq),
+						_ => unreachable!(),
+					}
+				},
+				'<' if self.source.get(token_start + 1).is_some_and(|c| *c == b'<') => {
+					token_end += 1;
+					Token::Symbol(Symbol::Shl)
+				},
+				'>' if self.source.get(token_start + 1).is_some_and(|c| *c == b'>') => {
+					token_end += 1;
+					Token::Symbol(Symbol::Shr)
+				},
+
 				'=' => Token::Symbol(Symbol::Eq),
 				'!' => Token::Symbol(Symbol::No),
 				'<' => Token::Symbol(Symbol::Lt),
@@ -251,6 +425,11 @@ impl<'a> Lexer<'a> {
 					Token::Symbol(Symbol::Or)
 				},
 
+				'&' => Token::Symbol(Symbol::BitAnd),
+				'|' => Token::Symbol(Symbol::BitOr),
+				'^' => Token::Symbol(Symbol::BitXor),
+				'~' => Token::Symbol(Symbol::BitNot),
+
 				c => return Err(self.error(ParseErrorKind::UnexpectedCharacter(c))),
 			};
 
@@ -267,6 +446,12 @@ pub enum Symbol {
 	Semicolon,
 	Comma,
 	Dot,
+	/// `?.`, member access, index or call that gives `null` for a `null` value
+	QuestionDot,
+	DotDot,
+	DotDotEq,
+	Ellipsis,
+	FatArrow,
 
 	ParenLeft,
 	ParenRight,
@@ -280,6 +465,8 @@ pub enum Symbol {
 	Mul,
 	Div,
 	Mod,
+	Pow,
+	FloorDiv,
 
 	EqEq,
 	Eq,
@@ -292,29 +479,61 @@ pub enum Symbol {
 
 	And,
 	Or,
+	/// `??`
+	QuestionQuestion,
+
+	BitAnd,
+	BitOr,
+	BitXor,
+	BitNot,
+	Shl,
+	Shr,
+
+	AddEq,
+	SubEq,
+	MulEq,
+	DivEq,
+	ModEq,
+	BitAndEq,
+	BitOrEq,
+	BitXorEq,
+	ShlEq,
+	ShrEq,
+
+	/// Never produced by the lexer, the keyword `in` is treated as this symbol in expressions
+	In,
 }
 impl Symbol {
 	pub fn prefix_bp(self) -> Option<((), u8)> {
 		Some(match self {
-			Self::Sub | Self::No => ((), 9),
+			Self::Sub | Self::No | Self::BitNot => ((), 21),
 			_ => return None,
 		})
 	}
 
 	pub fn postfix_bp(self) -> Option<(u8, ())> {
 		Some(match self {
-			Self::SquareLeft | Self::ParenLeft => (10, ()),
+			Self::SquareLeft | Self::ParenLeft | Self::QuestionDot => (24, ()),
 			_ => return None,
 		})
 	}
 
 	pub fn infix_bp(self) -> Option<(u8, u8)> {
 		Some(match self {
-			Self::And | Self::Or => (1, 2),
-			Self::EqEq | Self::NoEq | Self::Lt | Self::LtEq | Self::Gt | Self::GtEq => (3, 4),
-			Self::Add | Self::Sub => (5, 6),
-			Self::Mul | Self::Div | Self::Mod => (7, 8),
-			Self::Dot => (12, 11),
+			Self::QuestionQuestion => (1, 2),
+			Self::And | Self::Or => (3, 4),
+			Self::EqEq | Self::NoEq | Self::Lt | Self::LtEq | Self::Gt | Self::GtEq | Self::In => {
+				(5, 6)
+			},
+			Self::DotDot | Self::DotDotEq => (7, 8),
+			Self::BitOr => (9, 10),
+			Self::BitXor => (11, 12),
+			Self::BitAnd => (13, 14),
+			Self::Shl | Self::Shr => (15, 16),
+			Self::Add | Self::Sub => (17, 18),
+			Self::Mul | Self::Div | Self::Mod | Self::FloorDiv => (19, 20),
+			Self::Pow => (23, 22),
+			Self::Dot => (26, 25),
 			_ => return None,
 		})
 	}
@@ -328,6 +547,7 @@ impl Display for Token {
 			Self::Fn => write!(f, "keyword 'fn'"),
 			Self::Return => write!(f, "keyword 'return'"),
 			Self::Let => write!(f, "keyword 'let'"),
+			Self::Const => write!(f, "keyword 'const'"),
 			Self::If => write!(f, "keyword 'if'"),
 			Self::Else => write!(f, "keyword 'else'"),
 			Self::Loop => write!(f, "keyword 'loop'"),
@@ -336,11 +556,23 @@ impl Display for Token {
 			Self::For => write!(f, "keyword 'for'"),
 			Self::In => write!(f, "keyword 'in'"),
 			Self::While => write!(f, "keyword 'while'"),
+			Self::By => write!(f, "keyword 'by'"),
+			Self::Try => write!(f, "keyword 'try'"),
+			Self::Catch => write!(f, "keyword 'catch'"),
+			Self::Finally => write!(f, "keyword 'finally'"),
+			Self::Throw => write!(f, "keyword 'throw'"),
+			Self::Match => write!(f, "keyword 'match'"),
+			Self::Import => write!(f, "keyword 'import'"),
+			Self::From => write!(f, "keyword 'from'"),
+			Self::As => write!(f, "keyword 'as'"),
+			Self::Export => write!(f, "keyword 'export'"),
+			Self::Yield => write!(f, "keyword 'yield'"),
 
 			Self::Identifier(i) => write!(f, "identifier '{}'", i.0),
 			Self::Integer(n) => write!(f, "integer '{n}'"),
 			Self::Float(n) => write!(f, "float '{n}'"),
 			Self::String(s) => write!(f, "string \"{s}\""),
+			Self::Label(l) => write!(f, "label '{}", l.0),
 
 			Self::Eof => write!(f, "end of file"),
 
@@ -352,6 +584,11 @@ impl Display for Token {
 					Symbol::Semicolon => ";",
 					Symbol::Comma => ",",
 					Symbol::Dot => ".",
+					Symbol::QuestionDot => "?.",
+					Symbol::DotDot => "..",
+					Symbol::DotDotEq => "..=",
+					Symbol::Ellipsis => "...",
+					Symbol::FatArrow => "=>",
 
 					Symbol::ParenLeft => "(",
 					Symbol::ParenRight => ")",
@@ -365,6 +602,8 @@ impl Display for Token {
 					Symbol::Mul => "*",
 					Symbol::Div => "/",
 					Symbol::Mod => "%",
+					Symbol::Pow => "**",
+					Symbol::FloorDiv => "//",
 
 					Symbol::EqEq => "==",
 					Symbol::Eq => "=",
@@ -377,6 +616,27 @@ impl Display for Token {
 
 					Symbol::And => "&&",
 					Symbol::Or => "||",
+					Symbol::QuestionQuestion => "??",
+
+					Symbol::BitAnd => "&",
+					Symbol::BitOr => "|",
+					Symbol::BitXor => "^",
+					Symbol::BitNot => "~",
+					Symbol::Shl => "<<",
+					Symbol::Shr => ">>",
+
+					Symbol::AddEq => "+=",
+					Symbol::SubEq => "-=",
+					Symbol::MulEq => "*=",
+					Symbol::DivEq => "/=",
+					Symbol::ModEq => "%=",
+					Symbol::BitAndEq => "&=",
+					Symbol::BitOrEq => "|=",
+					Symbol::BitXorEq => "^=",
+					Symbol::ShlEq => "<<=",
+					Symbol::ShrEq => ">>=",
+
+					Symbol::In => "in",
 				}
 			),
 		}
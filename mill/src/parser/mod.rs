@@ -1,14 +1,32 @@
 mod error;
 mod expression;
 mod lexer;
+mod pattern;
 mod statement;
 
 use crate::ast::Ast;
 
 pub type LineNumber = usize;
-pub use error::{ParseError, ParseErrorKind};
+pub use error::{ParseError, ParseErrorKind, ParseWarning, ParseWarningKind};
 
 pub fn parse<Source: AsRef<str>>(source: Source) -> Result<Ast, error::ParseError> {
-	let mut lexer = lexer::Lexer::new(source.as_ref());
-	Ok(Ast(statement::parse_file(&mut lexer)?))
+	parse_source(source.as_ref(), None)
+}
+/// Like `parse`, but errors and warnings are located in the named file,
+/// which is also where imports are resolved from
+pub fn parse_module<Source: AsRef<str>>(
+	source: Source,
+	source_file: String,
+) -> Result<Ast, error::ParseError> {
+	parse_source(source.as_ref(), Some(source_file))
+}
+fn parse_source(source: &str, source_file: Option<String>) -> Result<Ast, error::ParseError> {
+	let mut lexer = lexer::Lexer::new(source, source_file.clone());
+	let (statements, exports) = statement::parse_file(&mut lexer)?;
+	Ok(Ast {
+		source_file,
+		statements,
+		exports,
+		warnings: lexer.take_warnings(),
+	})
 }
@@ -0,0 +1,158 @@
+use crate::{
+	ast::{Expression, Identifier, Pattern},
+	parser::{
+		error::{ParseError, ParseErrorKind},
+		lexer::{Lexer, Symbol, Token},
+	},
+};
+
+pub fn parse_pattern(lexer: &mut Lexer) -> Result<Pattern, ParseError> {
+	let mut alternatives = vec![parse_alternative(lexer)?];
+
+	while lexer.peek()? == Token::Symbol(Symbol::BitOr) {
+		lexer.next()?;
+		alternatives.push(parse_alternative(lexer)?);
+	}
+
+	Ok(if alternatives.len() == 1 {
+		alternatives.pop().unwrap()
+	} else {
+		Pattern::Or(alternatives)
+	})
+}
+
+fn parse_alternative(lexer: &mut Lexer) -> Result<Pattern, ParseError> {
+	Ok(match lexer.next()? {
+		Token::Identifier(i) if i.0 == "_" => Pattern::Wildcard,
+		Token::Identifier(i) => Pattern::Binding(i),
+
+		Token::True => Pattern::Literal(Expression::True),
+		Token::False => Pattern::Literal(Expression::False),
+		Token::Null => Pattern::Literal(Expression::Null),
+		Token::Integer(n) => Pattern::Literal(Expression::Integer(n)),
+		Token::Float(n) => Pattern::Literal(Expression::Float(n)),
+		Token::String(s) => Pattern::Literal(Expression::String(s)),
+
+		Token::Symbol(Symbol::Sub) => match lexer.next()? {
+			Token::Integer(n) => Pattern::Literal(Expression::Integer(-n)),
+			Token::Float(n) => Pattern::Literal(Expression::Float(-n)),
+			t => {
+				return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+					expected: "number",
+					found: t,
+				}))
+			},
+		},
+
+		Token::Symbol(Symbol::ParenLeft) => {
+			let pattern = parse_pattern(lexer)?;
+			match lexer.next()? {
+				Token::Symbol(Symbol::ParenRight) => {},
+				t => {
+					return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+						expected: "closing parenthesis",
+						found: t,
+					}))
+				},
+			}
+			pattern
+		},
+		Token::Symbol(Symbol::SquareLeft) => {
+			let mut elements = Vec::new();
+			let mut rest = None;
+
+			loop {
+				match lexer.peek()? {
+					Token::Symbol(Symbol::SquareRight) => break,
+					Token::Symbol(Symbol::Ellipsis) => {
+						lexer.next()?;
+						rest = Some(Box::new(match lexer.peek()? {
+							Token::Identifier(_) => parse_alternative(lexer)?,
+							_ => Pattern::Wildcard,
+						}));
+						break;
+					},
+					_ => elements.push(parse_pattern(lexer)?),
+				}
+				match lexer.peek()? {
+					Token::Symbol(Symbol::Comma) => {
+						lexer.next()?;
+					},
+					_ => break,
+				}
+			}
+
+			match lexer.next()? {
+				Token::Symbol(Symbol::SquareRight) => {},
+				t => {
+					return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+						expected: "closing square bracket",
+						found: t,
+					}))
+				},
+			}
+
+			Pattern::Array(elements, rest)
+		},
+		Token::Symbol(Symbol::CurlyLeft) => {
+			let mut entries = Vec::new();
+
+			while let Some((key, shorthand)) = parse_map_key(lexer)? {
+				let pattern = match (lexer.peek()?, shorthand) {
+					(Token::Symbol(Symbol::Colon), _) => {
+						lexer.next()?;
+						parse_pattern(lexer)?
+					},
+					// `{name}` is short for `{name: name}`
+					(_, Some(name)) => Pattern::Binding(name),
+					(t, None) => {
+						return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+							expected: "colon",
+							found: t,
+						}))
+					},
+				};
+				entries.push((key, pattern));
+				match lexer.next()? {
+					Token::Symbol(Symbol::Comma) => {},
+					Token::Symbol(Symbol::CurlyRight) => break,
+					t => {
+						return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+							expected: "comma or closing curly bracket",
+							found: t,
+						}))
+					},
+				}
+			}
+
+			Pattern::Map(entries)
+		},
+		t => {
+			return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+				expected: "pattern",
+				found: t,
+			}))
+		},
+	})
+}
+/// Parses the key of a map pattern, or the closing curly bracket as `None`.
+/// A name is a string key, and also the variable bound when it has no pattern.
+pub fn parse_map_key(
+	lexer: &mut Lexer,
+) -> Result<Option<(Expression, Option<Identifier>)>, ParseError> {
+	Ok(Some(match lexer.next()? {
+		Token::Symbol(Symbol::CurlyRight) => return Ok(None),
+		Token::Identifier(i) => (Expression::String(i.0.clone()), Some(i)),
+		Token::String(s) => (Expression::String(s), None),
+		Token::Integer(n) => (Expression::Integer(n), None),
+		Token::True => (Expression::True, None),
+		Token::False => (Expression::False, None),
+		Token::Null => (Expression::Null, None),
+		t => {
+			return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+				expected: "map key or closing curly bracket",
+				found: t,
+			}))
+		},
+	}))
+}
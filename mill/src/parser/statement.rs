@@ -1,22 +1,66 @@
 use crate::{
-	ast::{BinaryOperation, Expression, Lvalue, Statement},
+	ast::{
+		ArrayElement, BinaryOperation, Expression, Identifier, Lvalue, MapEntry, Statement,
+		StatementKind,
+	},
 	parser::{
 		error::{ParseError, ParseErrorKind},
-		expression::parse_expression,
+		expression::{parse_expression, parse_match},
 		lexer::{Lexer, Symbol, Token},
+		pattern::{parse_map_key, parse_pattern},
+		LineNumber,
 	},
+	stack,
 };
 
 pub fn parse_statement(lexer: &mut Lexer) -> Result<Statement, ParseError> {
+	stack::grow(|| parse_statement_kind(lexer))
+}
+
+fn parse_statement_kind(lexer: &mut Lexer) -> Result<Statement, ParseError> {
 	let mut expect_semicolon = true;
-	let statement = match lexer.peek()? {
+	let line_number = lexer.line_number();
+	let label = parse_label(lexer)?;
+	let first_token = lexer.peek()?;
+	if label.is_some() && !matches!(first_token, Token::Loop | Token::For | Token::While) {
+		lexer.next()?;
+		return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+			expected: "loop after label",
+			found: first_token,
+		}));
+	}
+	let kind = match first_token {
+		Token::Yield => {
+			lexer.next()?;
+			let value = if lexer.peek()? == Token::Symbol(Symbol::Semicolon) {
+				Expression::Null
+			} else {
+				parse_expression(lexer)?
+			};
+			StatementKind::Yield(value)
+		},
 		Token::Break => {
 			lexer.next()?;
-			Statement::Break
+			let label = parse_target_label(lexer)?;
+			let value = if lexer.peek()? == Token::Symbol(Symbol::Semicolon) {
+				None
+			} else {
+				Some(parse_expression(lexer)?)
+			};
+			if value.is_some()
+				&& !lexer
+					.target_loop(label.as_ref())
+					.is_some_and(|target| target.has_value)
+			{
+				return Err(lexer.error(ParseErrorKind::BreakValueOutsideLoop));
+			}
+			StatementKind::Break { label, value }
 		},
 		Token::Continue => {
 			lexer.next()?;
-			Statement::Continue
+			StatementKind::Continue {
+				label: parse_target_label(lexer)?,
+			}
 		},
 		Token::Return => {
 			lexer.next()?;
@@ -25,85 +69,59 @@ pub fn parse_statement(lexer: &mut Lexer) -> Result<Statement, ParseError> {
 			} else {
 				parse_expression(lexer)?
 			};
-			Statement::Return(value)
+			StatementKind::Return(value)
 		},
 		Token::Loop => {
 			lexer.next()?;
 			expect_semicolon = false;
-			Statement::Loop {
-				body: parse_block(lexer)?,
+			StatementKind::Loop {
+				body: parse_loop_body(lexer, label.clone(), true)?,
+				label,
 			}
 		},
-		Token::Let => {
+		Token::Let | Token::Const => {
 			lexer.next()?;
+			let constant = first_token == Token::Const;
 
-			let name = match lexer.next()? {
-				Token::Identifier(i) => i,
-				t => {
-					return Err(lexer.error(ParseErrorKind::UnexpectedToken {
-						expected: "identifier",
-						found: t,
-					}))
-				},
-			};
+			let pattern = parse_pattern(lexer)?;
 
 			let initializer = match lexer.peek()? {
-				Token::Symbol(Symbol::Semicolon) => Expression::Null,
+				// A constant without a value would be useless
+				Token::Symbol(Symbol::Semicolon) if !constant => Expression::Null,
 				Token::Symbol(Symbol::Eq) => {
 					lexer.next()?;
 					parse_expression(lexer)?
 				},
 				t => {
 					return Err(lexer.error(ParseErrorKind::UnexpectedToken {
-						expected: "semicolon or initializer",
+						expected: if constant {
+							"initializer"
+						} else {
+							"semicolon or initializer"
+						},
 						found: t,
 					}))
 				},
 			};
 
-			Statement::Declaration { name, initializer }
+			StatementKind::Declaration {
+				pattern,
+				initializer,
+				constant,
+			}
 		},
 		Token::If => {
 			lexer.next()?;
 			expect_semicolon = false;
-
-			let mut branches = Vec::new();
-
-			{
-				let condition = parse_expression(lexer)?;
-				let body = parse_block(lexer)?;
-
-				branches.push((condition, body));
+			StatementKind::If {
+				branches: parse_if_branches(lexer)?,
 			}
-
-			while lexer.peek()? == Token::Else {
-				lexer.next()?;
-				if lexer.peek()? == Token::If {
-					lexer.next()?;
-					let condition = parse_expression(lexer)?;
-					let body = parse_block(lexer)?;
-					branches.push((condition, body));
-				} else {
-					branches.push((Expression::True, parse_block(lexer)?));
-					break;
-				}
-			}
-
-			Statement::If { branches }
 		},
 		Token::For => {
 			lexer.next()?;
 			expect_semicolon = false;
 
-			let loop_var = match lexer.next()? {
-				Token::Identifier(i) => i,
-				t => {
-					return Err(lexer.error(ParseErrorKind::UnexpectedToken {
-						expected: "identifier",
-						found: t,
-					}))
-				},
-			};
+			let pattern = parse_pattern(lexer)?;
 
 			match lexer.next()? {
 				Token::In => {},
@@ -117,10 +135,11 @@ pub fn parse_statement(lexer: &mut Lexer) -> Result<Statement, ParseError> {
 
 			let iterator = parse_expression(lexer)?;
 
-			let body = parse_block(lexer)?;
+			let body = parse_loop_body(lexer, label.clone(), false)?;
 
-			Statement::For {
-				loop_var,
+			StatementKind::For {
+				label,
+				pattern,
 				iterator,
 				body,
 			}
@@ -130,35 +149,131 @@ pub fn parse_statement(lexer: &mut Lexer) -> Result<Statement, ParseError> {
 			expect_semicolon = false;
 
 			let condition = parse_expression(lexer)?;
+			let body = parse_loop_body(lexer, label.clone(), false)?;
+			StatementKind::While {
+				label,
+				condition,
+				body,
+			}
+		},
+		Token::Throw => {
+			lexer.next()?;
+			StatementKind::Throw(parse_expression(lexer)?)
+		},
+		Token::Try => {
+			lexer.next()?;
+			expect_semicolon = false;
+
 			let body = parse_block(lexer)?;
-			Statement::While { condition, body }
+
+			let catch = if lexer.peek()? == Token::Catch {
+				lexer.next()?;
+				let name = match lexer.next()? {
+					Token::Identifier(i) => i,
+					t => {
+						return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+							expected: "identifier",
+							found: t,
+						}))
+					},
+				};
+				Some((name, parse_block(lexer)?))
+			} else {
+				None
+			};
+
+			let finally = if lexer.peek()? == Token::Finally {
+				lexer.next()?;
+				Some(parse_block(lexer)?)
+			} else {
+				None
+			};
+
+			if catch.is_none() && finally.is_none() {
+				let t = lexer.next()?;
+				return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+					expected: "keyword 'catch' or 'finally'",
+					found: t,
+				}));
+			}
+
+			StatementKind::Try {
+				body,
+				catch,
+				finally,
+			}
 		},
-		Token::Symbol(Symbol::CurlyLeft) => {
+		Token::Import => {
+			lexer.next()?;
+			let path = parse_module_path(lexer)?;
+			match lexer.next()? {
+				Token::As => {},
+				t => {
+					return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+						expected: "keyword 'as'",
+						found: t,
+					}))
+				},
+			}
+			StatementKind::Import {
+				path,
+				name: parse_identifier(lexer)?,
+			}
+		},
+		Token::From => {
+			lexer.next()?;
+			let path = parse_module_path(lexer)?;
+			match lexer.next()? {
+				Token::Import => {},
+				t => {
+					return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+						expected: "keyword 'import'",
+						found: t,
+					}))
+				},
+			}
+			let mut names = vec![parse_identifier(lexer)?];
+			while lexer.peek()? == Token::Symbol(Symbol::Comma) {
+				lexer.next()?;
+				names.push(parse_identifier(lexer)?);
+			}
+			StatementKind::ImportNames { path, names }
+		},
+		// Like a block, a `match` ends the statement with its closing curly bracket
+		Token::Match => {
+			lexer.next()?;
 			expect_semicolon = false;
-			Statement::Block {
-				body: parse_block(lexer)?,
+			let expression = parse_match(lexer)?;
+			// Though it can still be ended with a semicolon like other expressions
+			if lexer.peek()? == Token::Symbol(Symbol::Semicolon) {
+				lexer.next()?;
 			}
+			StatementKind::UnusedExpression(expression)
 		},
-		_ => {
-			let expression = parse_expression(lexer)?;
-			match lexer.peek()? {
-				Token::Symbol(Symbol::Semicolon) => Statement::UnusedExpression(expression),
-				Token::Symbol(Symbol::Eq) => {
-					let lvalue = try_as_lvalue(expression, lexer)?;
+		// A map being destructured looks like a block up to the `=` after it
+		Token::Symbol(Symbol::CurlyLeft) => {
+			let mut attempt = lexer.clone();
+			match parse_map_lvalue(&mut attempt) {
+				Ok(lvalue) if matches!(attempt.peek(), Ok(Token::Symbol(Symbol::Eq))) => {
+					*lexer = attempt;
 					lexer.next()?;
-					Statement::Assignment {
+					StatementKind::Assignment {
 						lvalue,
 						value: parse_expression(lexer)?,
 					}
 				},
-				t => {
-					return Err(lexer.error(ParseErrorKind::UnexpectedToken {
-						expected: "';' or '='",
-						found: t,
-					}))
+				_ => {
+					expect_semicolon = false;
+					StatementKind::Block {
+						body: parse_block(lexer)?,
+					}
 				},
 			}
 		},
+		_ => {
+			let expression = parse_expression(lexer)?;
+			return parse_expression_statement(lexer, expression, line_number);
+		},
 	};
 	if expect_semicolon {
 		match lexer.next()? {
@@ -171,7 +286,89 @@ pub fn parse_statement(lexer: &mut Lexer) -> Result<Statement, ParseError> {
 			},
 		}
 	}
-	Ok(statement)
+	Ok(Statement { line_number, kind })
+}
+/// Parses the rest of a statement starting with an expression.
+/// The semicolon can be left out after the last expression of a block, which gives the block its value.
+pub fn parse_expression_statement(
+	lexer: &mut Lexer,
+	expression: Expression,
+	line_number: LineNumber,
+) -> Result<Statement, ParseError> {
+	let kind = match lexer.peek()? {
+		Token::Symbol(Symbol::CurlyRight) => {
+			return Ok(Statement {
+				line_number,
+				kind: StatementKind::UnusedExpression(expression),
+			})
+		},
+		Token::Symbol(Symbol::Semicolon) => StatementKind::UnusedExpression(expression),
+		Token::Symbol(Symbol::Eq) => {
+			let lvalue = try_as_lvalue(expression, lexer)?;
+			lexer.next()?;
+			StatementKind::Assignment {
+				lvalue,
+				value: parse_expression(lexer)?,
+			}
+		},
+		Token::Symbol(op) if compound_assignment_operation(op).is_some() => {
+			if matches!(expression, Expression::Array(_) | Expression::Map(_)) {
+				return Err(lexer.error(ParseErrorKind::ExpressionNotAssignable));
+			}
+			let lvalue = try_as_lvalue(expression, lexer)?;
+			lexer.next()?;
+			StatementKind::CompoundAssignment {
+				lvalue,
+				operation: compound_assignment_operation(op).unwrap(),
+				value: parse_expression(lexer)?,
+			}
+		},
+		t => {
+			return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+				expected: "';', '=' or compound assignment",
+				found: t,
+			}))
+		},
+	};
+	match lexer.next()? {
+		Token::Symbol(Symbol::Semicolon) => {},
+		t => {
+			return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+				expected: "semicolon",
+				found: t,
+			}))
+		},
+	}
+	Ok(Statement { line_number, kind })
+}
+/// Parses the conditions and blocks of an `if` after the keyword.
+/// An `else` block is given the condition `true`.
+pub fn parse_if_branches(
+	lexer: &mut Lexer,
+) -> Result<Vec<(Expression, Vec<Statement>)>, ParseError> {
+	let mut branches = Vec::new();
+
+	{
+		let condition = parse_expression(lexer)?;
+		let body = parse_block(lexer)?;
+
+		branches.push((condition, body));
+	}
+
+	while lexer.peek()? == Token::Else {
+		lexer.next()?;
+		if lexer.peek()? == Token::If {
+			lexer.next()?;
+			let condition = parse_expression(lexer)?;
+			let body = parse_block(lexer)?;
+			branches.push((condition, body));
+		} else {
+			branches.push((Expression::True, parse_block(lexer)?));
+			break;
+		}
+	}
+
+	Ok(branches)
 }
 pub fn parse_block(lexer: &mut Lexer) -> Result<Vec<Statement>, ParseError> {
 	match lexer.next()? {
@@ -183,9 +380,13 @@ pub fn parse_block(lexer: &mut Lexer) -> Result<Vec<Statement>, ParseError> {
 			}))
 		},
 	}
-
-	let mut statements = Vec::new();
-
+	parse_block_rest(lexer, Vec::new())
+}
+/// Parses the statements of a block after the ones already parsed, up to the closing curly bracket
+pub fn parse_block_rest(
+	lexer: &mut Lexer,
+	mut statements: Vec<Statement>,
+) -> Result<Vec<Statement>, ParseError> {
 	loop {
 		match lexer.peek()? {
 			Token::Symbol(Symbol::CurlyRight) => break,
@@ -212,24 +413,239 @@ pub fn parse_block(lexer: &mut Lexer) -> Result<Vec<Statement>, ParseError> {
 
 	Ok(statements)
 }
-pub fn parse_file(lexer: &mut Lexer) -> Result<Vec<Statement>, ParseError> {
+/// Parses the body of a loop, which `break` and `continue` in it can refer to
+pub fn parse_loop_body(
+	lexer: &mut Lexer,
+	label: Option<Identifier>,
+	has_value: bool,
+) -> Result<Vec<Statement>, ParseError> {
+	lexer.enter_loop(label, has_value);
+	let body = parse_block(lexer);
+	lexer.exit_loop();
+	body
+}
+/// Parses `'label:` if the next token is a label
+pub fn parse_label(lexer: &mut Lexer) -> Result<Option<Identifier>, ParseError> {
+	let Token::Label(label) = lexer.peek()? else {
+		return Ok(None);
+	};
+	lexer.next()?;
+	match lexer.next()? {
+		Token::Symbol(Symbol::Colon) => Ok(Some(label)),
+		t => Err(lexer.error(ParseErrorKind::UnexpectedToken {
+			expected: "colon after label",
+			found: t,
+		})),
+	}
+}
+/// Parses the label of a `break` or `continue`, which must belong to a surrounding loop
+fn parse_target_label(lexer: &mut Lexer) -> Result<Option<Identifier>, ParseError> {
+	let Token::Label(label) = lexer.peek()? else {
+		return Ok(None);
+	};
+	lexer.next()?;
+	if lexer.target_loop(Some(&label)).is_none() {
+		return Err(lexer.error(ParseErrorKind::UnknownLabel(label)));
+	}
+	Ok(Some(label))
+}
+/// Parses the top level of a file, returning its statements and the names it exports
+pub fn parse_file(lexer: &mut Lexer) -> Result<(Vec<Statement>, Vec<Identifier>), ParseError> {
 	let mut statements = Vec::new();
+	let mut exports = Vec::new();
 
 	loop {
-		if lexer.peek()? == Token::Eof {
-			break;
+		match lexer.peek()? {
+			Token::Eof => break,
+			Token::Export => {
+				lexer.next()?;
+				if !matches!(lexer.peek()?, Token::Let | Token::Const) {
+					let t = lexer.next()?;
+					return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+						expected: "keyword 'let' or 'const'",
+						found: t,
+					}));
+				}
+				let statement = parse_statement(lexer)?;
+				if let StatementKind::Declaration { pattern, .. } = &statement.kind {
+					exports.extend(pattern.bindings());
+				}
+				statements.push(statement);
+			},
+			_ => statements.push(parse_statement(lexer)?),
 		}
-
-		statements.push(parse_statement(lexer)?);
 	}
 
-	Ok(statements)
+	Ok((statements, exports))
+}
+fn parse_module_path(lexer: &mut Lexer) -> Result<String, ParseError> {
+	match lexer.next()? {
+		Token::String(path) => Ok(path),
+		t => Err(lexer.error(ParseErrorKind::UnexpectedToken {
+			expected: "module path",
+			found: t,
+		})),
+	}
+}
+fn parse_identifier(lexer: &mut Lexer) -> Result<Identifier, ParseError> {
+	match lexer.next()? {
+		Token::Identifier(i) => Ok(i),
+		t => Err(lexer.error(ParseErrorKind::UnexpectedToken {
+			expected: "identifier",
+			found: t,
+		})),
+	}
 }
 fn try_as_lvalue(expression: Expression, lexer: &mut Lexer) -> Result<Lvalue, ParseError> {
 	Ok(match expression {
 		Expression::Identifier(i) => Lvalue::Identifier(i),
-		Expression::Member(e, m) => Lvalue::Member(e, m),
-		Expression::BinaryOperation(ei, BinaryOperation::Index) => Lvalue::Index(ei),
+		Expression::Member(e, m) if e.is_place() => Lvalue::Member(e, m),
+		Expression::BinaryOperation(ei, BinaryOperation::Index) if ei[0].is_place() => {
+			Lvalue::Index(ei)
+		},
+		Expression::Array(mut elements) => {
+			let rest = match elements.pop() {
+				Some(ArrayElement::Spread(e)) => Some(Box::new(try_as_lvalue(e, lexer)?)),
+				Some(element) => {
+					elements.push(element);
+					None
+				},
+				None => None,
+			};
+			let lvalues = elements
+				.into_iter()
+				.map(|element| match element {
+					ArrayElement::Single(e) => try_as_lvalue(e, lexer),
+					// Only the last element can collect the rest
+					ArrayElement::Spread(_) => {
+						Err(lexer.error(ParseErrorKind::ExpressionNotAssignable))
+					},
+				})
+				.collect::<Result<_, _>>()?;
+			Lvalue::Array(lvalues, rest)
+		},
+		Expression::Map(entries) => Lvalue::Map(
+			entries
+				.into_iter()
+				.map(|entry| match entry {
+					MapEntry::Pair(k, e) => Ok((literal_key(k, lexer)?, try_as_lvalue(e, lexer)?)),
+					MapEntry::Spread(_) => {
+						Err(lexer.error(ParseErrorKind::ExpressionNotAssignable))
+					},
+				})
+				.collect::<Result<_, _>>()?,
+		),
 		_ => return Err(lexer.error(ParseErrorKind::ExpressionNotAssignable)),
 	})
 }
+/// Parses a map being destructured, from its opening curly bracket.
+/// Keys are written as in a map pattern, so `{name}` assigns to `name`.
+fn parse_map_lvalue(lexer: &mut Lexer) -> Result<Lvalue, ParseError> {
+	match lexer.next()? {
+		Token::Symbol(Symbol::CurlyLeft) => {},
+		t => {
+			return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+				expected: "map",
+				found: t,
+			}))
+		},
+	}
+	let mut entries = Vec::new();
+	while let Some((key, shorthand)) = parse_map_key(lexer)? {
+		let lvalue = match (lexer.peek()?, shorthand) {
+			(Token::Symbol(Symbol::Colon), _) => {
+				lexer.next()?;
+				if lexer.peek()? == Token::Symbol(Symbol::CurlyLeft) {
+					parse_map_lvalue(lexer)?
+				} else {
+					let expression = parse_expression(lexer)?;
+					try_as_lvalue(expression, lexer)?
+				}
+			},
+			(_, Some(name)) => Lvalue::Identifier(name),
+			(t, None) => {
+				return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+					expected: "colon",
+					found: t,
+				}))
+			},
+		};
+		entries.push((key, lvalue));
+		match lexer.next()? {
+			Token::Symbol(Symbol::Comma) => {},
+			Token::Symbol(Symbol::CurlyRight) => break,
+			t => {
+				return Err(lexer.error(ParseErrorKind::UnexpectedToken {
+					expected: "comma or closing curly bracket",
+					found: t,
+				}))
+			},
+		}
+	}
+	Ok(Lvalue::Map(entries))
+}
+/// Checks that a key of a map being destructured is one a map pattern could have,
+/// where a name stands for the string rather than a variable
+fn literal_key(key: Expression, lexer: &Lexer) -> Result<Expression, ParseError> {
+	match key {
+		Expression::Identifier(name) => Ok(Expression::String(name.0)),
+		key @ (Expression::String(_)
+		| Expression::Integer(_)
+		| Expression::True
+		| Expression::False
+		| Expression::Null) => Ok(key),
+		_ => Err(lexer.error(ParseErrorKind::ExpressionNotAssignable)),
+	}
+}
+fn compound_assignment_operation(symbol: Symbol) -> Option<BinaryOperation> {
+	Some(match symbol {
+		Symbol::AddEq => BinaryOperation::Add,
+		Symbol::SubEq => BinaryOperation::Sub,
+		Symbol::MulEq => BinaryOperation::Mul,
+		Symbol::DivEq => BinaryOperation::Div,
+		Symbol::ModEq => BinaryOperation::Mod,
+		Symbol::BitAndEq => BinaryOperation::BitAnd,
+		Symbol::BitOrEq => BinaryOperation::BitOr,
+		Symbol::BitXorEq => BinaryOperation::BitXor,
+		Symbol::ShlEq => BinaryOperation::Shl,
+		Symbol::ShrEq => BinaryOperation::Shr,
+		_ => return None,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::parser::{parse, ParseErrorKind};
+
+	#[test]
+	fn break_values_need_a_loop_expression() {
+		assert!(parse("let x = loop { break 1; };").is_ok());
+		assert!(parse("'outer: loop { for i in 0..1 { break 'outer 1; } }").is_ok());
+		for source in [
+			"break 1;",
+			"for i in 0..1 { break 1; }",
+			"loop { let f = fn() { break 1; }; }",
+			"'outer: while true { loop { break 'outer 1; } }",
+		] {
+			assert!(
+				matches!(
+					parse(source).map_err(|error| error.kind),
+					Err(ParseErrorKind::BreakValueOutsideLoop)
+				),
+				"{source}"
+			);
+		}
+		assert!(matches!(
+			parse("loop { break 'missing; }").map_err(|error| error.kind),
+			Err(ParseErrorKind::UnknownLabel(_))
+		));
+	}
+	#[test]
+	fn yield_in_a_block_with_a_value() {
+		assert!(parse("let f = fn() { if true { yield 1; } };").is_ok());
+		assert!(matches!(
+			parse("let f = fn() { let x = { yield 1; 2 }; };").map_err(|error| error.kind),
+			Err(ParseErrorKind::YieldInExpression)
+		));
+	}
+}
@@ -0,0 +1,9 @@
+/// Free stack below which nested code moves to a new stack segment.
+/// Has to fit the frames between two checks, which are big in debug builds.
+const RED_ZONE: usize = 256 * 1024;
+/// Size of the stack segments allocated for deeply nested code
+const SEGMENT: usize = 4 * 1024 * 1024;
+
+/// Runs code that recurses once per level of nesting in a script, growing the stack onto
+/// the heap when it runs low, so deeply nested scripts can't overflow the host's stack
+pub fn grow<T>(run: impl FnOnce() -> T) -> T { stacker::maybe_grow(RED_ZONE, SEGMENT, run) }